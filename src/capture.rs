@@ -0,0 +1,172 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+use crate::msg::{LogMessage, LogSeverity};
+
+/// How many of the most recent msgs of each thread are kept for [crate::assert_logged!]
+const HISTORY_CAPACITY: usize = 256;
+
+thread_local! {
+    static CAPTURES: RefCell<Vec<Vec<String>>> = const { RefCell::new(Vec::new()) };
+    static HISTORY: RefCell<VecDeque<(LogSeverity, String)>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Pops the innermost capture, even if the captured closure panics
+struct CaptureFrame;
+
+impl CaptureFrame {
+    fn push() -> Self {
+        CAPTURES.with(|captures| captures.borrow_mut().push(Vec::new()));
+        CaptureFrame
+    }
+}
+
+impl Drop for CaptureFrame {
+    fn drop(&mut self) {
+        CAPTURES.with(|captures| captures.borrow_mut().pop());
+    }
+}
+
+/// Runs the closure and returns the msg (%m) of everything logged on the current thread meanwhile
+/// The msgs are still published to every registered [crate::sink::Sink].
+/// Nested captures each receive every msg logged during their own closure.
+pub fn with_capture<F: FnOnce()>(f: F) -> Vec<String> {
+    let frame = CaptureFrame::push();
+    f();
+    let captured = CAPTURES.with(|captures| captures.borrow_mut().last_mut().map(std::mem::take));
+    drop(frame);
+    captured.unwrap_or_default()
+}
+
+#[doc(hidden)]
+/// Used by publish! to hand every msg to the active captures of the current thread
+pub fn record(msg: &LogMessage) {
+    CAPTURES.with(|captures| {
+        for capture in captures.borrow_mut().iter_mut() {
+            capture.push(msg.msg.to_string());
+        }
+    });
+
+    #[cfg(debug_assertions)]
+    HISTORY.with(|history| {
+        let mut history = history.borrow_mut();
+        // reuse the allocation of the evicted msg
+        let mut text = match history.len() >= HISTORY_CAPACITY {
+            true => history
+                .pop_front()
+                .map(|(_, text)| text)
+                .unwrap_or_default(),
+            false => String::new(),
+        };
+        text.clear();
+        text.push_str(msg.msg);
+        history.push_back((msg.severity, text));
+    });
+}
+
+#[doc(hidden)]
+/// Use assert_logged! instead
+/// Panics with every recently logged msg of the current thread, unless one of them matches.
+pub fn assert_logged(severity: LogSeverity, matches: impl Fn(&str) -> bool, expected: &str) {
+    if !cfg!(debug_assertions) {
+        panic!("assert_logged! requires debug assertions, the msgs are not recorded otherwise");
+    }
+
+    HISTORY.with(|history| {
+        let history = history.borrow();
+        if history
+            .iter()
+            .any(|(logged, text)| *logged == severity && matches(text))
+        {
+            return;
+        }
+
+        let mut logged = String::new();
+        for (severity, text) in history.iter() {
+            logged.push_str(&format!("\n  [{}]: {}", severity.to_string(), text));
+        }
+        if logged.is_empty() {
+            logged.push_str("\n  nothing");
+        }
+        panic!(
+            "expected a msg at {} which {} to be logged on this thread, but only these were logged:{}",
+            severity.to_string().trim_end(),
+            expected,
+            logged
+        );
+    });
+}
+
+/// Asserts that a msg was logged on the current thread, e.g. `assert_logged!(Info, contains "started")` or `assert_logged!(Warn, "disk full")`
+/// Only the most recent msgs of each thread are kept, and only if debug assertions are enabled.
+#[macro_export]
+macro_rules! assert_logged {
+    ($severity: tt, contains $text: expr) => {
+        $crate::capture::assert_logged(
+            $crate::msg::LogSeverity::$severity,
+            |msg| msg.contains($text),
+            &format!("contains {:?}", $text),
+        )
+    };
+    ($severity: tt, $text: expr) => {
+        $crate::capture::assert_logged(
+            $crate::msg::LogSeverity::$severity,
+            |msg| msg == $text,
+            &format!("equals {:?}", $text),
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{capture::with_capture, debug, info, msg, publish, warn};
+
+    #[test]
+    fn with_capture_works() {
+        info!("capture: before");
+        let mut inner = Vec::new();
+        let outer = with_capture(|| {
+            info!("capture: outer");
+            inner = with_capture(|| {
+                debug!("capture: inner");
+            });
+            warn!("capture: outer again");
+        });
+        info!("capture: after");
+
+        assert_eq!(
+            vec!["capture: outer", "capture: inner", "capture: outer again"],
+            outer
+        );
+        assert_eq!(vec!["capture: inner"], inner);
+    }
+
+    #[test]
+    fn with_capture_survives_panics() {
+        let result = std::panic::catch_unwind(|| {
+            with_capture(|| {
+                info!("capture: panicking");
+                panic!("capture: panicked");
+            })
+        });
+        assert!(result.is_err());
+
+        let captured = with_capture(|| info!("capture: after panic"));
+        assert_eq!(vec!["capture: after panic"], captured);
+    }
+
+    #[test]
+    fn assert_logged_works() {
+        info!("assert_logged: server started on port {}", 8080);
+        warn!("assert_logged: disk almost full");
+
+        assert_logged!(Info, contains "server started");
+        assert_logged!(Warn, "assert_logged: disk almost full");
+
+        let result = std::panic::catch_unwind(|| assert_logged!(Error, contains "server started"));
+        let err = result.unwrap_err();
+        let err = err.downcast_ref::<String>().unwrap();
+        assert!(err.contains("expected a msg at Error which contains \"server started\""));
+        assert!(err.contains("[Info ]: assert_logged: server started on port 8080"));
+        assert!(err.contains("[Warn ]: assert_logged: disk almost full"));
+    }
+}
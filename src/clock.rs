@@ -0,0 +1,88 @@
+use std::{
+    cell::RefCell,
+    sync::{Arc, OnceLock},
+    time::SystemTime,
+};
+
+use crate::{Error, Result};
+
+/// The source of the timestamps of logged msgs, e.g. a real-time clock on a board without a system clock
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> SystemTime;
+}
+
+/// Reads the time of the operating system, used unless another [crate::clock::Clock] is set
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+static CLOCK: OnceLock<Box<dyn Clock>> = OnceLock::new();
+
+thread_local! {
+    static CLOCK_OVERRIDE: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Sets the [crate::clock::Clock] which timestamps every msg, once and before the first msg is logged
+/// Fails if a clock was already set, msgs timestamped by different clocks could not be ordered.
+pub fn set_clock(clock: impl Clock) -> Result<()> {
+    CLOCK
+        .set(Box::new(clock))
+        .map_err(|_| Error::Config("the clock can only be set once".to_string()))
+}
+
+/// Overrides the [crate::clock::Clock] on the current thread, or restores the one of [crate::clock::set_clock] with `None`
+/// Intended for tests, which can not set the clock of the whole process
+pub fn override_clock(clock: Option<Arc<dyn Clock>>) {
+    CLOCK_OVERRIDE.with(|override_| *override_.borrow_mut() = clock);
+}
+
+/// Returns the current time of the [crate::clock::Clock], which every logged msg is timestamped with
+pub fn now() -> SystemTime {
+    let overridden = CLOCK_OVERRIDE.with(|clock| clock.borrow().as_ref().map(|clock| clock.now()));
+    match (overridden, CLOCK.get()) {
+        (Some(time), _) => time,
+        (None, Some(clock)) => clock.now(),
+        (None, None) => SystemTime::now(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{
+        clock::{now, override_clock, Clock},
+        msg,
+    };
+
+    /// Stands still at a fixed time, like a board whose clock was never set
+    struct FixedClock(SystemTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> SystemTime {
+            self.0
+        }
+    }
+
+    #[test]
+    fn clock_works() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_164_645);
+
+        override_clock(Some(Arc::new(FixedClock(time))));
+        let msg = msg!(Info, Default, "clock: fixed");
+        let fixed = now();
+        override_clock(None);
+
+        assert_eq!(time, msg.time);
+        assert_eq!("2024-01-02T03:04:05+00:00", msg.parse("%t"));
+        assert_eq!(time, fixed);
+        assert!(now() > time);
+    }
+}
@@ -0,0 +1,73 @@
+use std::{fmt::Display, io, sync::mpsc::SendError};
+
+/// The errors which can occur while setting up or operating logtra
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from or writing to a log target failed
+    Io(io::Error),
+    /// A template contains an invalid % pattern
+    TemplateParse(String),
+    /// A [crate::sink::SinkDeclaration] or sink option is invalid
+    Config(String),
+    /// A msg could not be handed over to another thread
+    Send(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::TemplateParse(err) => write!(f, "invalid template: {}", err),
+            Error::Config(err) => write!(f, "invalid configuration: {}", err),
+            Error::Send(err) => write!(f, "send error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(_err: SendError<T>) -> Self {
+        Error::Send("the receiving end of the channel is disconnected".to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{error::Error as _, io, sync::mpsc};
+
+    use crate::Error;
+
+    #[test]
+    fn io_error_converts() {
+        let err = Error::from(io::Error::new(io::ErrorKind::NotFound, "missing.log"));
+        assert!(matches!(err, Error::Io(_)));
+        assert!(err.source().is_some());
+        assert_eq!("io error: missing.log", err.to_string());
+    }
+
+    #[test]
+    fn send_error_converts() {
+        let (sender, receiver) = mpsc::channel::<u32>();
+        drop(receiver);
+
+        let err = Error::from(sender.send(1).unwrap_err());
+        assert!(matches!(err, Error::Send(_)));
+        assert!(err.source().is_none());
+    }
+}
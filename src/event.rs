@@ -0,0 +1,140 @@
+use std::fmt::Display;
+
+use crate::msg::{Color, LogMessage, LogSeverity, Style};
+
+/// Builds up a msg piece by piece and publishes it on [crate::event::Event::emit], see [crate::event!]
+/// Fields become the structured fields of the msg, rendered by %k and %F and written to the `fields` of JSON.
+#[derive(Debug, Clone)]
+pub struct Event {
+    module: &'static str,
+    file: &'static str,
+    line: u32,
+    severity: LogSeverity,
+    color: Color,
+    style: Style,
+    tag: Option<String>,
+    msg: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Event {
+    #[doc(hidden)]
+    /// Use event! instead, which captures the location of the call
+    pub fn new(severity: LogSeverity, module: &'static str, file: &'static str, line: u32) -> Self {
+        Event {
+            module,
+            file,
+            line,
+            severity,
+            color: severity.color(),
+            style: Style::NONE,
+            tag: None,
+            msg: String::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a structured field, like `key = value` after the `;` of info!
+    pub fn field(mut self, key: &str, value: impl Display) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the text style %[ starts together with the color
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn msg(mut self, msg: impl Display) -> Self {
+        self.msg = msg.to_string();
+        self
+    }
+
+    /// Publishes the msg on every registered [crate::sink::Sink]
+    pub fn emit(self) {
+        let fields: Vec<(&str, &str)> = self
+            .fields
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let msg = LogMessage {
+            line: self.line,
+            file: self.file,
+            time: crate::clock::now(),
+            module: self.module,
+            msg: &self.msg,
+            severity: self.severity,
+            color: self.color,
+            style: self.style,
+            tag: self.tag.as_deref(),
+            fields: &fields,
+            uptime: crate::msg::uptime(),
+        };
+        crate::publish!(&msg);
+    }
+}
+
+/// Starts an [crate::event::Event] at the given severity, e.g. `event!(Info).field("user", name).msg("logged in").emit()`
+#[macro_export]
+macro_rules! event {
+    ($severity: tt) => {
+        $crate::event::Event::new(
+            $crate::msg::LogSeverity::$severity,
+            module_path!(),
+            file!(),
+            line!(),
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        capture::with_capture,
+        msg::{Color, LogSeverity},
+        sink::{MemorySink, SinkDeclaration},
+    };
+
+    #[test]
+    fn event_builder_works() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "event".to_string(),
+            severity: LogSeverity::Debug,
+            module: module_path!().to_string(),
+            template: "%m [%k]".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        crate::sink!(sink);
+
+        let captured = with_capture(|| {
+            crate::event!(Info)
+                .field("user", "ada")
+                .field("attempt", 2)
+                .color(Color::Orange)
+                .msg("event: logged in")
+                .emit();
+            crate::event!(Debug).msg("event: no fields").emit();
+        });
+
+        assert_eq!(vec!["event: logged in", "event: no fields"], captured);
+        assert_eq!(
+            vec![
+                "event: logged in [user=ada attempt=2]",
+                "event: no fields []"
+            ],
+            *lines.lock().unwrap()
+        );
+    }
+}
@@ -1,47 +1,481 @@
-use sink::Sink;
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock, RwLock, Weak,
+    },
+};
 
+use msg::{Color, LogMessage, LogSeverity, Style};
+use sink::{Sink, SinkConfigEntry};
+
+pub mod capture;
+pub mod clock;
+pub mod error;
+pub mod event;
+pub mod mdc;
 pub mod msg;
+pub mod reader;
+pub mod recorder;
+#[cfg(unix)]
+pub mod signal;
 pub mod sink;
+pub mod skew;
+
+pub use error::{Error, Result};
+
+/// A registered [crate::sink::Sink], behind its own lock so threads can log to different sinks at once
+type SharedSink = Arc<Mutex<Box<dyn Sink>>>;
 
-static mut SINKS: Vec<Box<dyn Sink>> = Vec::new();
+/// Refers to a registered [crate::sink::Sink] without keeping it alive once it is removed
+type WeakSink = Weak<Mutex<Box<dyn Sink>>>;
+
+/// The registered sinks, replaced as a whole whenever one is added or removed
+/// Readers only hold the lock to clone the current snapshot, so publishing never waits for another thread to finish logging,
+/// and registering never waits for a slow sink. A snapshot taken before an update keeps logging to the previous sinks.
+struct Registry {
+    snapshot: RwLock<Arc<Vec<SharedSink>>>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        Registry {
+            snapshot: RwLock::new(Arc::new(Vec::new())),
+        }
+    }
+
+    fn snapshot(&self) -> Arc<Vec<SharedSink>> {
+        // a panic while updating can not leave a half written snapshot behind
+        self.snapshot
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Applies the change to a copy of the current sinks and publishes it as the new snapshot
+    fn update<R>(&self, f: impl FnOnce(&mut Vec<SharedSink>) -> R) -> R {
+        let mut snapshot = self.snapshot.write().unwrap_or_else(|err| err.into_inner());
+        let mut sinks = snapshot.as_ref().clone();
+        let result = f(&mut sinks);
+        *snapshot = Arc::new(sinks);
+        result
+    }
+
+    fn register(&self, sink: Box<dyn Sink>) {
+        self.update(|sinks| sinks.push(Arc::new(Mutex::new(sink))));
+    }
+
+    fn set_all_severities(&self, severity: LogSeverity) -> SeverityToken {
+        let previous = self
+            .snapshot()
+            .iter()
+            .map(|sink| {
+                let mut locked = lock(sink);
+                let previous = locked.severity();
+                locked.set_severity(severity);
+                (Arc::downgrade(sink), previous)
+            })
+            .collect();
+        SeverityToken(previous)
+    }
+
+    fn config(&self) -> Vec<SinkConfigEntry> {
+        self.snapshot()
+            .iter()
+            .filter_map(|sink| lock(sink).config())
+            .collect()
+    }
+
+    /// Reads the current configs and builds the changed sinks before taking the write lock,
+    /// so neither a slow sink nor a slow build blocks registering. The write lock only swaps the prepared sinks.
+    /// Sinks registered meanwhile are left untouched.
+    fn reconcile(&self, desired: Vec<SinkConfigEntry>) -> Result<Reconciliation> {
+        let current: Vec<(SharedSink, Option<SinkConfigEntry>)> = self
+            .snapshot()
+            .iter()
+            .map(|sink| (sink.clone(), lock(sink).config()))
+            .collect();
+
+        let mut changes = Vec::new();
+        for entry in desired.iter() {
+            let registered = current.iter().find(|(_, config)| {
+                config
+                    .as_ref()
+                    .is_some_and(|config| config.decl.name == entry.decl.name)
+            });
+            match registered {
+                Some((_, Some(config))) if config == entry => (),
+                _ => changes.push((
+                    entry.decl.name.clone(),
+                    registered.map(|(sink, _)| sink.clone()),
+                    Arc::new(Mutex::new(entry.build()?)),
+                )),
+            }
+        }
+        let removed: Vec<(SharedSink, String)> = current
+            .iter()
+            .filter_map(|(sink, config)| {
+                let name = &config.as_ref()?.decl.name;
+                (!desired.iter().any(|entry| entry.decl.name == *name))
+                    .then(|| (sink.clone(), name.clone()))
+            })
+            .collect();
+
+        Ok(self.update(|sinks| {
+            let mut reconciliation = Reconciliation::default();
+            sinks.retain(|sink| {
+                match removed
+                    .iter()
+                    .find(|(removed, _)| Arc::ptr_eq(removed, sink))
+                {
+                    Some((_, name)) => {
+                        reconciliation.removed.push(name.clone());
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            for (name, replaced, sink) in changes {
+                let registered = replaced.and_then(|replaced| {
+                    sinks
+                        .iter()
+                        .position(|registered| Arc::ptr_eq(registered, &replaced))
+                });
+                match registered {
+                    Some(index) => {
+                        sinks[index] = sink;
+                        reconciliation.updated.push(name);
+                    }
+                    None => {
+                        sinks.push(sink);
+                        reconciliation.added.push(name);
+                    }
+                }
+            }
+
+            reconciliation
+        }))
+    }
+
+    /// Runs the operation on every sink in ascending [crate::sink::Sink::flush_priority], then in registration order
+    /// Every sink is reached, even if an earlier one fails. Returns the first error.
+    fn for_each_sink(&self, f: impl Fn(&mut Box<dyn Sink>) -> Result<()>) -> Result<()> {
+        let mut sinks = self.snapshot().as_ref().clone();
+        // stable, so sinks of the same priority keep their registration order
+        sinks.sort_by_cached_key(|sink| lock(sink).flush_priority());
+
+        let mut result = Ok(());
+        for sink in sinks.iter() {
+            let done = f(&mut lock(sink));
+            if result.is_ok() {
+                result = done;
+            }
+        }
+        result
+    }
+
+    fn restore_severities(&self, token: SeverityToken) {
+        // sinks registered since are not in the token, removed ones are not in the snapshot
+        for sink in self.snapshot().iter() {
+            let previous = token
+                .0
+                .iter()
+                .find(|(previous, _)| std::ptr::eq(previous.as_ptr(), Arc::as_ptr(sink)));
+            if let Some((_, severity)) = previous {
+                lock(sink).set_severity(*severity);
+            }
+        }
+    }
+}
+
+static SINKS: OnceLock<Registry> = OnceLock::new();
+
+fn registry() -> &'static Registry {
+    SINKS.get_or_init(Registry::new)
+}
+
+/// The current snapshot of the registered sinks
+fn sinks() -> Arc<Vec<SharedSink>> {
+    registry().snapshot()
+}
+
+/// A panic inside a sink poisons its lock, which must not stop the other threads from logging
+fn lock(sink: &Mutex<Box<dyn Sink>>) -> MutexGuard<'_, Box<dyn Sink>> {
+    sink.lock().unwrap_or_else(|err| err.into_inner())
+}
 
 /// Registers a new [crate::sink::Sink]
 #[macro_export]
 macro_rules! sink {
     ($sink: tt) => {{
-        unsafe {
-            crate::SINKS.push(Box::new($sink));
-        }
+        crate::init();
+        crate::register(Box::new($sink));
+    }};
+}
+
+/// Registers a closure as [crate::sink::Sink], receiving every msg of at least [crate::msg::LogSeverity::Info] from every module
+#[macro_export]
+macro_rules! sink_fn {
+    ($f: expr) => {{
+        crate::init();
+        crate::register(Box::new(crate::sink::FnSink::new(
+            crate::sink::SinkDeclaration::default(),
+            $f,
+        )));
     }};
 }
 
+#[doc(hidden)]
+/// Use sink! instead
+pub fn register(sink: Box<dyn Sink>) {
+    registry().register(sink);
+}
+
+/// Tracks whether logtra was set up before the first msg was logged
+struct InitGuard {
+    initialized: AtomicBool,
+    strict: AtomicBool,
+    warned: AtomicBool,
+}
+
+impl InitGuard {
+    const fn new() -> Self {
+        InitGuard {
+            initialized: AtomicBool::new(false),
+            strict: AtomicBool::new(false),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns whether the msg about to be logged triggered the warning
+    fn check(&self) -> bool {
+        if !cfg!(debug_assertions)
+            || !self.strict.load(Ordering::Relaxed)
+            || self.initialized.load(Ordering::Relaxed)
+            || self.warned.swap(true, Ordering::Relaxed)
+        {
+            return false;
+        }
+
+        eprintln!("logtra: a msg was logged before any sink was registered or logtra::init() was called, see https://docs.rs/logtra");
+        true
+    }
+}
+
+static INIT: InitGuard = InitGuard::new();
+
+/// Marks logtra as set up, registering a [crate::sink::Sink] does so as well
+pub fn init() {
+    INIT.initialized.store(true, Ordering::Relaxed);
+}
+
+/// Returns whether [crate::init] was called or a [crate::sink::Sink] was registered
+pub fn is_initialized() -> bool {
+    INIT.initialized.load(Ordering::Relaxed)
+}
+
+/// Warns once on stderr if a msg is logged before logtra was set up, only in debug builds
+pub fn set_strict_init(strict: bool) {
+    INIT.strict.store(strict, Ordering::Relaxed);
+}
+
+thread_local! {
+    static PUBLISHING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[doc(hidden)]
+/// Used by publish! to drop msgs logged while a msg is published on the same thread, e.g. by a sink
+/// Publishing them would recurse endlessly if the sink logs on every msg.
+pub struct PublishGuard;
+
+impl PublishGuard {
+    pub fn enter() -> Option<Self> {
+        match PUBLISHING.with(|publishing| publishing.replace(true)) {
+            true => None,
+            false => Some(PublishGuard),
+        }
+    }
+}
+
+impl Drop for PublishGuard {
+    fn drop(&mut self) {
+        PUBLISHING.with(|publishing| publishing.set(false));
+    }
+}
+
+#[doc(hidden)]
+/// Used by publish! to warn about msgs logged before logtra was set up
+pub fn check_initialized() {
+    INIT.check();
+}
+
+/// The previous severities of every registered [crate::sink::Sink], as returned by [crate::set_all_severities]
+/// Refers to the sinks themselves, not their position in the registry, and does not keep removed sinks alive.
+pub struct SeverityToken(Vec<(WeakSink, LogSeverity)>);
+
+/// Sets the severity of every registered [crate::sink::Sink] at once
+/// Returns a [crate::SeverityToken] which can be passed to [crate::restore_severities]
+pub fn set_all_severities(severity: LogSeverity) -> SeverityToken {
+    registry().set_all_severities(severity)
+}
+
+/// Restores the severities replaced by [crate::set_all_severities]
+/// Sinks registered since keep their severity, sinks removed or replaced since are skipped.
+pub fn restore_severities(token: SeverityToken) {
+    registry().restore_severities(token)
+}
+
+/// Pauses or resumes every registered [crate::sink::Sink] with the given name
+/// A disabled sink drops every msg, but keeps its configuration and state, e.g. open connections
+/// Returns whether a sink with that name is registered
+pub fn set_sink_enabled(name: &str, enabled: bool) -> bool {
+    let mut found = false;
+    for sink in sinks().iter() {
+        let mut sink = lock(sink);
+        if sink.name() == name {
+            sink.set_enabled(enabled);
+            found = true;
+        }
+    }
+    found
+}
+
+/// Reopens the files of every registered [crate::sink::Sink] by name, see [crate::sink::Sink::reopen]
+/// Sinks are reopened in ascending [crate::sink::Sink::flush_priority], then in registration order.
+/// Every sink is reopened, even if an earlier one fails. Returns the first error.
+pub fn reopen_all() -> Result<()> {
+    registry().for_each_sink(|sink| sink.reopen())
+}
+
+/// Flushes every registered [crate::sink::Sink], see [crate::sink::Sink::flush], e.g. before a controlled shutdown
+/// Sinks are flushed in ascending [crate::sink::Sink::flush_priority], then in registration order.
+/// Every sink is flushed, even if an earlier one fails. Returns the first error.
+pub fn flush_all() -> Result<()> {
+    registry().for_each_sink(|sink| sink.flush())
+}
+
+/// Returns the configuration of every registered [crate::sink::Sink] which can be recreated from it
+pub fn current_config() -> Vec<SinkConfigEntry> {
+    registry().config()
+}
+
+/// Renders the msg with the template of every registered [crate::sink::Sink] which has a declaration, without logging it
+/// Returns the name of every sink with its rendered msg, e.g. to tune templates interactively.
+/// Ignores the filters of the sinks, a sink which would drop the msg is previewed as well.
+pub fn preview(msg: &LogMessage) -> Vec<(String, String)> {
+    sinks()
+        .iter()
+        .filter_map(|sink| {
+            let mut sink = lock(sink);
+            let decl = sink.declaration_mut()?;
+            Some((decl.name.clone(), msg.render(decl)))
+        })
+        .collect()
+}
+
+/// The names of the sinks changed by [crate::reconcile]
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Reconciliation {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub updated: Vec<String>,
+}
+
+/// Applies the minimal set of registry changes to match the desired configuration, matching sinks by name
+/// Sinks which can not be recreated from a [crate::sink::SinkConfigEntry] are left untouched.
+/// Fails without changing the registry if any desired sink can not be created.
+pub fn reconcile(desired: Vec<SinkConfigEntry>) -> Result<Reconciliation> {
+    registry().reconcile(desired)
+}
+
+/// Publishes an already formatted line to every registered [crate::sink::Sink]
+/// The line becomes the msg (%m) of an otherwise empty [crate::msg::LogMessage], so sink filters still apply
+pub fn publish_raw_line(line: &str, severity: LogSeverity) {
+    registry().publish_raw_line(line, severity)
+}
+
+impl Registry {
+    fn publish_raw_line(&self, line: &str, severity: LogSeverity) {
+        let msg = LogMessage {
+            time: clock::now(),
+            module: "",
+            file: "",
+            line: 0,
+            msg: line,
+            severity,
+            color: Color::Default,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: msg::uptime(),
+        };
+        publish!(@to self.snapshot(), &msg);
+    }
+}
+
 /// Creates a new [crate::msg::LogMessage]
+/// Structured fields follow the format arguments after a `;`, e.g. `info!("request done"; status = 200, path = "/x")`
 #[macro_export]
 macro_rules! msg {
-    ($severity: tt, $color: tt, $($arg:tt)*) => {
+    (@split $severity: tt, $color: tt, [$($arg:tt)*] ; $($key: ident = $value: expr),* $(,)?) => {
+        crate::msg!(@build $severity, $color, [$($arg)*], [$((stringify!($key), &*($value).to_string())),*])
+    };
+    (@split $severity: tt, $color: tt, [$($arg:tt)*] $next: tt $($rest:tt)*) => {
+        crate::msg!(@split $severity, $color, [$($arg)* $next] $($rest)*)
+    };
+    (@split $severity: tt, $color: tt, [$($arg:tt)*]) => {
+        crate::msg!(@build $severity, $color, [$($arg)*], [])
+    };
+    (@build $severity: tt, $color: tt, [$($arg:tt)*], [$($fields:tt)*]) => {
         crate::msg::LogMessage {
             line: line!(),
             file: file!(),
-            time: chrono::Utc::now().into(),
+            time: crate::clock::now(),
             module: module_path!(),
             msg: &format_args!($($arg)*).to_string(),
             severity: crate::msg::LogSeverity::$severity,
             color: crate::msg::Color::$color,
+            style: crate::msg::Style::NONE,
+            tag: None,
+            fields: &[$($fields)*],
+            uptime: crate::msg::uptime(),
         }
     };
+    ($severity: tt, $color: tt, $($arg:tt)*) => {
+        crate::msg!(@split $severity, $color, [] $($arg)*)
+    };
 }
 /// Takes a [crate::msg::LogMessage] and tries to log it on every registered [crate::sink::Sink]
+/// `publish!(@to sinks, msg)` logs on the given sinks instead, e.g. the snapshot of another registry
 #[macro_export]
 macro_rules! publish {
-    ($msg: expr) => {
-        unsafe {
-            for i in 0..crate::SINKS.len() {
-                crate::SINKS.get_mut(i).unwrap().log_filtered($msg);
-            }
+    (@to $sinks: expr, $msg: expr) => {{
+        if let Some(_guard) = crate::PublishGuard::enter() {
+            crate::check_initialized();
+            let skewed = crate::skew::check($msg);
+            let msg = skewed.as_ref().unwrap_or($msg);
+            crate::mdc::with_thread_fields(msg, |msg| {
+                crate::capture::record(msg);
+                crate::recorder::record(msg);
+                for sink in $sinks.iter() {
+                    crate::lock(sink).log_filtered(msg);
+                }
+            });
         }
+    }};
+    ($msg: expr) => {
+        crate::publish!(@to crate::sinks(), $msg)
     };
 }
 
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {{
+        let msg = msg!(Verbose, DimGrey, $($arg)*);
+        publish!(&msg);
+    }};
+}
 #[macro_export]
 macro_rules! trace {
     ($($arg:tt)*) => {{
@@ -84,10 +518,27 @@ macro_rules! fatal {
         publish!(&msg);
     }};
 }
+/// Logs a message under a logical tag, e.g. `tagged!("auth", Info, "user {} logged in", name)`
+#[macro_export]
+macro_rules! tagged {
+    (@ $tag: expr, $severity: tt, $color: tt, $($arg:tt)*) => {{
+        let mut msg = msg!($severity, $color, $($arg)*);
+        msg.tag = Some($tag);
+        publish!(&msg);
+    }};
+    ($tag: expr, Verbose, $($arg:tt)*) => { tagged!(@ $tag, Verbose, DimGrey, $($arg)*) };
+    ($tag: expr, Trace, $($arg:tt)*) => { tagged!(@ $tag, Trace, Grey, $($arg)*) };
+    ($tag: expr, Debug, $($arg:tt)*) => { tagged!(@ $tag, Debug, Blue, $($arg)*) };
+    ($tag: expr, Info, $($arg:tt)*) => { tagged!(@ $tag, Info, Default, $($arg)*) };
+    ($tag: expr, Warn, $($arg:tt)*) => { tagged!(@ $tag, Warn, Orange, $($arg)*) };
+    ($tag: expr, Error, $($arg:tt)*) => { tagged!(@ $tag, Error, Red, $($arg)*) };
+    ($tag: expr, Fatal, $($arg:tt)*) => { tagged!(@ $tag, Fatal, DarkRed, $($arg)*) };
+}
 #[doc(hidden)]
 /// Use log! instead
 pub fn log<T: std::fmt::Debug + ?Sized>(severity: crate::msg::LogSeverity, name: &str, obj: &T) {
     match severity {
+        msg::LogSeverity::Verbose => verbose!("{}: {:?}", name, obj),
         msg::LogSeverity::Trace => trace!("{}: {:?}", name, obj),
         msg::LogSeverity::Debug => debug!("{}: {:?}", name, obj),
         msg::LogSeverity::Info => info!("{}: {:?}", name, obj),
@@ -105,6 +556,33 @@ macro_rules! log {
         crate::log(crate::msg::LogSeverity::$severity, stringify!($obj), $obj)
     };
 }
+#[doc(hidden)]
+/// Use log_pretty! instead
+pub fn log_pretty<T: std::fmt::Debug + ?Sized>(
+    severity: crate::msg::LogSeverity,
+    name: &str,
+    obj: &T,
+) {
+    match severity {
+        msg::LogSeverity::Verbose => verbose!("{}: {:#?}", name, obj),
+        msg::LogSeverity::Trace => trace!("{}: {:#?}", name, obj),
+        msg::LogSeverity::Debug => debug!("{}: {:#?}", name, obj),
+        msg::LogSeverity::Info => info!("{}: {:#?}", name, obj),
+        msg::LogSeverity::Warn => warn!("{}: {:#?}", name, obj),
+        msg::LogSeverity::Error => error!("{}: {:#?}", name, obj),
+        msg::LogSeverity::Fatal => fatal!("{}: {:#?}", name, obj),
+    }
+}
+/// Like log!, but pretty prints the object over multiple lines
+#[macro_export]
+macro_rules! log_pretty {
+    ($obj: expr) => {
+        log_pretty!(Debug, $obj)
+    };
+    ($severity: tt, $obj: expr) => {
+        crate::log_pretty(crate::msg::LogSeverity::$severity, stringify!($obj), $obj)
+    };
+}
 #[macro_export]
 macro_rules! fatal_assert {
     ($val: expr) => {
@@ -135,15 +613,48 @@ macro_rules! time {
 
 #[cfg(test)]
 mod test {
-    use std::{time::SystemTime};
+    use std::{
+        sync::{atomic::Ordering, Arc, Mutex},
+        time::{Duration, SystemTime},
+    };
 
     use chrono::Utc;
 
     use crate::{
-        msg::{LogSeverity},
-        sink::{SinkDeclaration, VoidSink},
+        capture::with_capture,
+        init, is_initialized, lock,
+        msg::{Base64, Color, LogMessage, LogSeverity, Style},
+        preview, set_sink_enabled,
+        sink::{
+            CountingRenderSink, FileSink, MemorySink, Sink, SinkConfigEntry, SinkDeclaration,
+            SinkKind, VoidSink,
+        },
+        InitGuard, Registry,
     };
 
+    struct RecordingSink {
+        severity: LogSeverity,
+        lines: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Sink for RecordingSink {
+        fn log(&mut self, msg: &LogMessage) {
+            self.lines.lock().unwrap().push(msg.parse("%m"));
+        }
+
+        fn severity(&self) -> LogSeverity {
+            self.severity
+        }
+
+        fn set_severity(&mut self, severity: LogSeverity) {
+            self.severity = severity;
+        }
+
+        fn module(&self) -> &str {
+            ""
+        }
+    }
+
     #[test]
     fn log_macros() {
         let sink = VoidSink::new(SinkDeclaration {
@@ -151,18 +662,23 @@ mod test {
             severity: LogSeverity::Trace,
             module: "".to_string(),
             template: "[%t][%c][%[%i%]][%s][%f:%l]: %m\n".to_string(),
+            ..Default::default()
         });
         sink!(sink);
 
         let now: SystemTime = Utc::now().into();
 
+        verbose!("Hello World: Verbose!");
         trace!("Hello World: Trace!");
         debug!("Hello World: Debug!");
         info!("Hello World: Info!");
         warn!("Hello World: Warn!");
         error!("Hello World: Error!");
         fatal!("Hello World: Fatal!");
+        tagged!("auth", Info, "Hello World: {}!", "Tagged");
+        tagged!("auth", Verbose, "Hello World: {}!", "Tagged");
         log!(Info, &now);
+        log!(Verbose, &now);
         log!(Info, &Some(now));
         log!(Info, &None as &Option<String>);
         log!(Info, &Ok::<&str, &str>("error"));
@@ -173,6 +689,473 @@ mod test {
         fatal_assert!(&(now == now));
     }
 
+    #[test]
+    fn set_all_severities_works() {
+        let registry = Registry::new();
+        let recording = |severity| RecordingSink {
+            severity,
+            lines: Arc::new(Mutex::new(Vec::new())),
+        };
+        registry.register(Box::new(recording(LogSeverity::Info)));
+        registry.register(Box::new(recording(LogSeverity::Error)));
+        let severities = || -> Vec<LogSeverity> {
+            registry
+                .snapshot()
+                .iter()
+                .map(|sink| lock(sink).severity())
+                .collect()
+        };
+
+        let token = registry.set_all_severities(LogSeverity::Trace);
+        assert_eq!(vec![LogSeverity::Trace, LogSeverity::Trace], severities());
+
+        // the first sink is removed and another one registered before restoring
+        registry.update(|sinks| {
+            sinks.remove(0);
+        });
+        registry.register(Box::new(recording(LogSeverity::Warn)));
+        registry.restore_severities(token);
+        assert_eq!(vec![LogSeverity::Error, LogSeverity::Warn], severities());
+    }
+
+    #[test]
+    fn sink_fn_works() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let captured = received.clone();
+        sink_fn!(move |msg: &LogMessage| {
+            if msg.msg.starts_with("sink_fn: ") {
+                captured.lock().unwrap().push(msg.msg.to_string());
+            }
+        });
+
+        info!("sink_fn: first");
+        debug!("sink_fn: filtered");
+        error!("sink_fn: second");
+
+        assert_eq!(
+            vec!["sink_fn: first", "sink_fn: second"],
+            *received.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn structured_fields_work() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "fields".to_string(),
+            severity: LogSeverity::Info,
+            module: module_path!().to_string(),
+            template: "%m [%k]".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        sink!(sink);
+
+        let path = "/x";
+        info!("fields: request done"; status = 200, path = path);
+        warn!("fields: {} of {}", 1, 2; attempt = 1 + 1,);
+        info!("fields: none");
+        tagged!("audit", Info, "fields: tagged"; user = "ada");
+
+        let lines: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with("fields: "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec![
+                "fields: request done [status=200 path=/x]",
+                "fields: 1 of 2 [attempt=2]",
+                "fields: none []",
+                "fields: tagged [user=ada]",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn base64_fields_work() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "base64".to_string(),
+            severity: LogSeverity::Info,
+            module: module_path!().to_string(),
+            template: "%m [%k]".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        sink!(sink);
+
+        let packet: &[u8] = &[0xde, 0xad, 0xbe, 0xef];
+        info!("base64: packet"; payload = Base64(packet), digest = Base64(b"logtra"));
+
+        let lines: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with("base64: "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec!["base64: packet [payload=3q2+7w== digest=bG9ndHJh]"],
+            lines
+        );
+    }
+
+    #[test]
+    fn publish_raw_line_works() {
+        // raw lines have no module or tag to filter by, so they go to a registry of their own
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "memory".to_string(),
+            severity: LogSeverity::Info,
+            template: "%m".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        let registry = Registry::new();
+        registry.register(Box::new(sink));
+
+        registry.publish_raw_line("raw: [12:00][Info] first", LogSeverity::Info);
+        registry.publish_raw_line("raw: [12:01][Debug] filtered", LogSeverity::Debug);
+        registry.publish_raw_line("raw: [12:02][Warn] second", LogSeverity::Warn);
+        registry.publish_raw_line("raw: [12:03][Error] third", LogSeverity::Error);
+
+        assert_eq!(
+            vec![
+                "raw: [12:00][Info] first",
+                "raw: [12:02][Warn] second",
+                "raw: [12:03][Error] third"
+            ],
+            *lines.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn set_sink_enabled_works() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "set_sink_enabled".to_string(),
+            module: module_path!().to_string(),
+            template: "%m".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        sink!(sink);
+        let recorded = |marker: &str| lines.lock().unwrap().iter().any(|l| l == marker);
+
+        assert!(set_sink_enabled("set_sink_enabled", false));
+        info!("set_sink_enabled: disabled");
+        assert!(!recorded("set_sink_enabled: disabled"));
+
+        assert!(set_sink_enabled("set_sink_enabled", true));
+        info!("set_sink_enabled: enabled");
+        assert!(recorded("set_sink_enabled: enabled"));
+
+        assert!(!set_sink_enabled("set_sink_enabled: missing", false));
+    }
+
+    #[test]
+    fn flush_all_works() {
+        use std::fs::{read_to_string, remove_file};
+
+        let path =
+            std::env::temp_dir().join(format!("logtra-{}-flush-all.log", std::process::id()));
+        let _ = remove_file(&path);
+        let mut file = FileSink::new(SinkDeclaration {
+            name: path.to_str().unwrap().to_string(),
+            template: "%m\n".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        file.log_filtered(&msg!(Info, Default, "flush_all: first"));
+        file.log_filtered(&msg!(Info, Default, "flush_all: second"));
+        let registry = Registry::new();
+        registry.register(Box::new(file));
+        assert!(read_to_string(&path).is_err());
+
+        assert!(registry.for_each_sink(|sink| sink.flush()).is_ok());
+        assert_eq!(
+            "flush_all: first\nflush_all: second\n",
+            read_to_string(&path).unwrap()
+        );
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[test]
+    fn flush_priority_works() {
+        struct OrderedSink {
+            name: &'static str,
+            priority: i32,
+            flushed: Arc<Mutex<Vec<&'static str>>>,
+        }
+
+        impl Sink for OrderedSink {
+            fn log(&mut self, _msg: &LogMessage) {}
+
+            fn severity(&self) -> LogSeverity {
+                LogSeverity::Trace
+            }
+
+            fn module(&self) -> &str {
+                ""
+            }
+
+            fn flush(&mut self) -> crate::Result<()> {
+                self.flushed.lock().unwrap().push(self.name);
+                Ok(())
+            }
+
+            fn flush_priority(&self) -> i32 {
+                self.priority
+            }
+        }
+
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+        let registry = Registry::new();
+        for (name, priority) in [("forwarder", 1), ("first", 0), ("early", -1), ("second", 0)] {
+            registry.register(Box::new(OrderedSink {
+                name,
+                priority,
+                flushed: flushed.clone(),
+            }));
+        }
+
+        assert!(registry.for_each_sink(|sink| sink.flush()).is_ok());
+        assert_eq!(
+            vec!["early", "first", "second", "forwarder"],
+            *flushed.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn preview_works() {
+        // never logs anything, as no module matches
+        let declaration = |name: &str, template: &str| SinkDeclaration {
+            name: name.to_string(),
+            module: "preview: no module".to_string(),
+            template: template.to_string(),
+            ..Default::default()
+        };
+        let short = MemorySink::new(declaration("preview: short", "%m"));
+        let long = MemorySink::new(declaration("preview: long", "[%i][%s][%f:%l]: %m"));
+        sink!(short);
+        sink!(long);
+
+        let msg = LogMessage {
+            time: SystemTime::UNIX_EPOCH,
+            module: "logtra",
+            file: "src/main.rs",
+            line: 7,
+            msg: "previewed",
+            severity: LogSeverity::Warn,
+            color: Color::Default,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: Duration::ZERO,
+        };
+        let mut previews: Vec<(String, String)> = preview(&msg)
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("preview: "))
+            .collect();
+        previews.sort();
+        assert_eq!(
+            vec![
+                (
+                    "preview: long".to_string(),
+                    "[Warn ][logtra][src/main.rs:7]: previewed".to_string()
+                ),
+                ("preview: short".to_string(), "previewed".to_string()),
+            ],
+            previews
+        );
+    }
+
+    #[test]
+    fn reconcile_works() {
+        let void = |name: &str, severity: LogSeverity| SinkConfigEntry {
+            kind: SinkKind::Void,
+            decl: SinkDeclaration {
+                name: name.to_string(),
+                severity,
+                ..Default::default()
+            },
+        };
+        let registry = Registry::new();
+        // can not be recreated from a config, so it is left untouched
+        registry.register(Box::new(RecordingSink {
+            severity: LogSeverity::Info,
+            lines: Arc::new(Mutex::new(Vec::new())),
+        }));
+
+        let reconciliation = registry
+            .reconcile(vec![
+                void("a", LogSeverity::Info),
+                void("b", LogSeverity::Info),
+            ])
+            .unwrap();
+        assert_eq!(vec!["a", "b"], reconciliation.added);
+        assert!(reconciliation.removed.is_empty());
+
+        let reconciliation = registry
+            .reconcile(vec![
+                void("b", LogSeverity::Warn),
+                void("c", LogSeverity::Info),
+            ])
+            .unwrap();
+        assert_eq!(vec!["c"], reconciliation.added);
+        assert_eq!(vec!["a"], reconciliation.removed);
+        assert_eq!(vec!["b"], reconciliation.updated);
+
+        assert_eq!(
+            vec![void("b", LogSeverity::Warn), void("c", LogSeverity::Info)],
+            registry.config()
+        );
+        assert_eq!(3, registry.snapshot().len());
+
+        let invalid = SinkConfigEntry {
+            kind: SinkKind::File,
+            decl: SinkDeclaration::default(),
+        };
+        assert!(registry.reconcile(vec![invalid]).is_err());
+        assert_eq!(3, registry.snapshot().len());
+    }
+
+    #[test]
+    fn init_guard_works() {
+        let guard = InitGuard::new();
+        assert!(!guard.check());
+
+        guard.strict.store(true, Ordering::Relaxed);
+        assert!(guard.check());
+        assert!(!guard.check());
+
+        let guard = InitGuard::new();
+        guard.strict.store(true, Ordering::Relaxed);
+        guard.initialized.store(true, Ordering::Relaxed);
+        assert!(!guard.check());
+
+        init();
+        assert!(is_initialized());
+    }
+
+    #[test]
+    fn log_pretty_macro() {
+        #[allow(dead_code)]
+        #[derive(Debug)]
+        struct Inner {
+            id: u32,
+        }
+        #[allow(dead_code)]
+        #[derive(Debug)]
+        struct Outer {
+            name: &'static str,
+            inner: Inner,
+        }
+        let outer = Outer {
+            name: "pretty",
+            inner: Inner { id: 7 },
+        };
+
+        let captured = with_capture(|| {
+            log_pretty!(Info, &outer);
+            log!(Info, &outer);
+        });
+        assert_eq!(
+            vec![
+                "&outer: Outer {\n    name: \"pretty\",\n    inner: Inner {\n        id: 7,\n    },\n}",
+                "&outer: Outer { name: \"pretty\", inner: Inner { id: 7 } }"
+            ],
+            captured
+        );
+    }
+
+    #[test]
+    fn reentrant_logging_is_dropped() {
+        struct ReentrantSink {
+            calls: Arc<Mutex<usize>>,
+        }
+
+        impl Sink for ReentrantSink {
+            fn log(&mut self, _msg: &LogMessage) {
+                *self.calls.lock().unwrap() += 1;
+                tagged!("reentrant", Info, "reentrant: nested");
+            }
+
+            fn severity(&self) -> LogSeverity {
+                LogSeverity::Trace
+            }
+
+            fn module(&self) -> &str {
+                ""
+            }
+
+            fn tag(&self) -> Option<&str> {
+                Some("reentrant")
+            }
+        }
+
+        struct Noisy;
+
+        impl std::fmt::Debug for Noisy {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                info!("reentrant: from debug");
+                write!(f, "Noisy")
+            }
+        }
+
+        let calls = Arc::new(Mutex::new(0));
+        let sink = ReentrantSink {
+            calls: calls.clone(),
+        };
+        sink!(sink);
+
+        let captured = with_capture(|| {
+            tagged!("reentrant", Info, "reentrant: outer");
+            log!(Info, &Noisy);
+        });
+        assert_eq!(1, *calls.lock().unwrap());
+        assert_eq!(
+            vec!["reentrant: outer", "reentrant: from debug", "&Noisy: Noisy"],
+            captured
+        );
+    }
+
+    #[test]
+    fn concurrent_logging_works() {
+        const THREADS: usize = 8;
+        const MSGS: usize = if cfg!(miri) { 100 } else { 10_000 };
+
+        let sink = CountingRenderSink::new(SinkDeclaration {
+            name: "concurrent".to_string(),
+            severity: LogSeverity::Trace,
+            tag: Some("concurrent".to_string()),
+            ..Default::default()
+        });
+        let rendered = sink.counter();
+        sink!(sink);
+        let void = VoidSink::new(SinkDeclaration {
+            name: "concurrent void".to_string(),
+            severity: LogSeverity::Trace,
+            ..Default::default()
+        });
+        sink!(void);
+
+        let threads: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                std::thread::spawn(move || {
+                    for i in 0..MSGS {
+                        tagged!("concurrent", Info, "concurrent: {} {}", thread, i);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(THREADS * MSGS, rendered.load(Ordering::Relaxed));
+    }
+
     #[test]
     fn time_macro() {
         let sink = VoidSink::new(SinkDeclaration {
@@ -180,6 +1163,7 @@ mod test {
             severity: LogSeverity::Trace,
             module: "".to_string(),
             template: "[%t][%c][%[%i%]][%s][%f:%l]: %m\n".to_string(),
+            ..Default::default()
         });
         sink!(sink);
 
@@ -194,39 +1178,261 @@ mod test {
 
 #[cfg(test)]
 mod performance {
-    use std::time::SystemTime;
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc::{channel, Receiver, Sender},
+            Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant, SystemTime},
+    };
 
     use chrono::Utc;
 
     use crate::{
-        msg::LogSeverity,
-        sink::{SinkDeclaration, VoidSink},
+        lock,
+        msg::{Color, CompiledTemplate, LogMessage, LogSeverity, Style},
+        sink::{CountingRenderSink, Sink, SinkDeclaration, VoidSink},
+        Registry,
     };
 
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts the allocations per thread, so a test is not disturbed by the tests running next to it
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // the counter may already be gone while its thread exits
+            let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+            System.realloc(ptr, layout, new_size)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Returns how often f allocated on the current thread
+    fn allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    #[test]
+    fn template_interning_allocations() {
+        let msg = LogMessage {
+            time: Utc::now().into(),
+            module: "logtra::msg",
+            file: "msg.rs",
+            line: 42,
+            msg: "Hello World!",
+            severity: LogSeverity::Info,
+            color: Color::Default,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: Duration::ZERO,
+        };
+        let pattern = "severity=%i module=%s location=%f:%l message=\"%m\" (rendered by logtra)\n";
+        const RENDERS: usize = 1_000;
+
+        // interns the template on this thread
+        msg.parse(pattern);
+
+        let compiled = allocations(|| {
+            for _ in 0..RENDERS {
+                std::hint::black_box(msg.parse_compiled(&CompiledTemplate::new(pattern)));
+            }
+        });
+        let interned = allocations(|| {
+            for _ in 0..RENDERS {
+                std::hint::black_box(msg.parse(pattern));
+            }
+        });
+
+        println!(
+            "Rendered {} msgs with {} allocations compiling every time, {} interned",
+            RENDERS, compiled, interned
+        );
+        assert!(interned < compiled);
+    }
+
     #[test]
     fn log_performance() {
-        let sink = VoidSink::new(SinkDeclaration {
+        let sink = CountingRenderSink::new(SinkDeclaration {
             name: "void".to_string(),
             severity: LogSeverity::Trace,
             module: "".to_string(),
             template: "[%t][%[%i%]][%s][%f:%l]: %m\n".to_string(),
+            ..Default::default()
         });
+        let rendered = sink.counter();
         sink!(sink);
 
         for _ in 0..10 {
             let start: SystemTime = Utc::now().into();
+            let rendered_before = rendered.load(Ordering::Relaxed);
             let mut counter = 0;
             while start.elapsed().unwrap().as_millis() < 1000 {
                 info!("Hello World: Info!");
                 counter += 1;
             }
+            let rendered = rendered.load(Ordering::Relaxed) - rendered_before;
 
             println!(
-                "Processed {} infos in {}ms",
+                "Processed {} infos ({} rendered) in {}ms",
                 counter,
+                rendered,
                 start.elapsed().unwrap().as_millis()
             );
+            assert!(rendered >= counter);
             assert!(counter > 100000);
         }
     }
+
+    /// Takes its time to log, like a sink writing to a slow disk or network
+    struct SlowSink {
+        decl: SinkDeclaration,
+        logging: Sender<()>,
+        logged: Arc<AtomicBool>,
+    }
+
+    impl Sink for SlowSink {
+        fn log(&mut self, _msg: &LogMessage) {
+            let _ = self.logging.send(());
+            thread::sleep(Duration::from_millis(100));
+            self.logged.store(true, Ordering::SeqCst);
+        }
+
+        fn severity(&self) -> LogSeverity {
+            self.decl.severity
+        }
+
+        fn set_severity(&mut self, severity: LogSeverity) {
+            self.decl.severity = severity;
+        }
+
+        fn module(&self) -> &str {
+            &self.decl.module
+        }
+    }
+
+    fn slow_sink(decl: &SinkDeclaration) -> (SlowSink, Receiver<()>, Arc<AtomicBool>) {
+        let (logging, receiver) = channel();
+        let logged = Arc::new(AtomicBool::new(false));
+        let sink = SlowSink {
+            decl: decl.clone(),
+            logging,
+            logged: logged.clone(),
+        };
+        (sink, receiver, logged)
+    }
+
+    /// Registers a sink while another thread is in the middle of logging to a slow sink
+    /// Returns how long the registration took and whether it had to wait for the slow sink
+    fn register_while_logging(
+        log: impl FnOnce() + Send + 'static,
+        register: impl FnOnce(),
+        logging: Receiver<()>,
+        logged: Arc<AtomicBool>,
+    ) -> (Duration, bool) {
+        let logger = thread::spawn(log);
+        logging.recv().unwrap();
+
+        let start = Instant::now();
+        register();
+        let elapsed = start.elapsed();
+        let waited = logged.load(Ordering::SeqCst);
+
+        logger.join().unwrap();
+        (elapsed, waited)
+    }
+
+    #[test]
+    fn registry_contention() {
+        let msg = LogMessage {
+            time: SystemTime::now(),
+            module: module_path!(),
+            file: file!(),
+            line: line!(),
+            msg: "registry contention",
+            severity: LogSeverity::Info,
+            color: Color::Default,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: Duration::ZERO,
+        };
+        let decl = SinkDeclaration {
+            name: "slow".to_string(),
+            severity: LogSeverity::Trace,
+            ..Default::default()
+        };
+
+        // a single lock held while logging, as the registry used to be
+        let (sink, logging, logged) = slow_sink(&decl);
+        let naive: Arc<Mutex<Vec<Box<dyn Sink>>>> = Arc::new(Mutex::new(vec![Box::new(sink)]));
+        let (naive_elapsed, naive_waited) = register_while_logging(
+            {
+                let naive = naive.clone();
+                move || {
+                    for sink in naive.lock().unwrap().iter_mut() {
+                        sink.log_filtered(&msg);
+                    }
+                }
+            },
+            || {
+                naive
+                    .lock()
+                    .unwrap()
+                    .push(Box::new(VoidSink::new(decl.clone())))
+            },
+            logging,
+            logged,
+        );
+
+        let (sink, logging, logged) = slow_sink(&decl);
+        let registry = Arc::new(Registry::new());
+        registry.update(|sinks| sinks.push(Arc::new(Mutex::new(Box::new(sink)))));
+        let (snapshot_elapsed, snapshot_waited) = register_while_logging(
+            {
+                let registry = registry.clone();
+                move || {
+                    for sink in registry.snapshot().iter() {
+                        lock(sink).log_filtered(&msg);
+                    }
+                }
+            },
+            || {
+                registry.update(|sinks| {
+                    sinks.push(Arc::new(Mutex::new(Box::new(VoidSink::new(decl.clone())))))
+                })
+            },
+            logging,
+            logged,
+        );
+
+        println!(
+            "Registered a sink while logging in {:?} with a single lock, {:?} with snapshots",
+            naive_elapsed, snapshot_elapsed
+        );
+        assert!(naive_waited);
+        assert!(!snapshot_waited);
+        assert_eq!(2, registry.snapshot().len());
+    }
 }
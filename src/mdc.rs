@@ -0,0 +1,368 @@
+use std::{cell::RefCell, marker::PhantomData};
+
+use crate::msg::LogMessage;
+
+thread_local! {
+    /// The mapped diagnostic context of the current thread, in the order the keys were first put
+    static MDC: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+    /// The fields of the enclosing [crate::scope_fields!] blocks of the current thread, outermost first
+    static SCOPES: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Attaches the key-value to every msg logged on the current thread until it is removed, rendered by %X
+/// The key-value is also a structured field of the msg, rendered by %k and %F and written by JSON and OTLP sinks,
+/// unless a field of the msg itself or of a [crate::scope_fields!] block has the same key.
+/// Putting a key again replaces its value in place. The context is dropped together with its thread.
+pub fn mdc_put(key: &str, value: impl ToString) {
+    let value = value.to_string();
+    MDC.with(|mdc| {
+        let mut mdc = mdc.borrow_mut();
+        match mdc.iter_mut().find(|(existing, _)| existing == key) {
+            Some((_, existing)) => *existing = value,
+            None => mdc.push((key.to_string(), value)),
+        }
+    });
+}
+
+/// Returns the value of the key in the context of the current thread
+pub fn mdc_get(key: &str) -> Option<String> {
+    MDC.with(|mdc| {
+        mdc.borrow()
+            .iter()
+            .find(|(existing, _)| existing == key)
+            .map(|(_, value)| value.clone())
+    })
+}
+
+/// Detaches the key from the msgs of the current thread, returning its value
+pub fn mdc_remove(key: &str) -> Option<String> {
+    MDC.with(|mdc| {
+        let mut mdc = mdc.borrow_mut();
+        let position = mdc.iter().position(|(existing, _)| existing == key)?;
+        Some(mdc.remove(position).1)
+    })
+}
+
+/// Detaches every key from the msgs of the current thread
+pub fn mdc_clear() {
+    MDC.with(|mdc| mdc.borrow_mut().clear());
+}
+
+/// Puts the key-value into the context of the current thread until the returned guard is dropped, rendered by %x{key}
+/// Dropping the guard restores the value the key had before, so nested scopes may put the same key again.
+pub fn push_context(key: &str, value: impl ToString) -> ContextGuard {
+    let previous = mdc_get(key);
+    mdc_put(key, value);
+    ContextGuard {
+        key: key.to_string(),
+        previous,
+        _thread: PhantomData,
+    }
+}
+
+/// Restores the previous value of its key when dropped, see [crate::mdc::push_context]
+/// Bound to the thread it was created on, as the context is.
+#[must_use = "the key-value is removed again once the guard is dropped"]
+pub struct ContextGuard {
+    key: String,
+    previous: Option<String>,
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(previous) => mdc_put(&self.key, previous),
+            None => {
+                mdc_remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Attaches the fields to every msg logged on the current thread until the returned guard is dropped, see [crate::scope_fields!]
+pub fn push_scope_fields(fields: &[(&str, String)]) -> ScopeGuard {
+    let depth = SCOPES.with(|scopes| {
+        let mut scopes = scopes.borrow_mut();
+        let depth = scopes.len();
+        scopes.extend(
+            fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone())),
+        );
+        depth
+    });
+    ScopeGuard {
+        depth,
+        _thread: PhantomData,
+    }
+}
+
+/// Detaches the fields of its scope when dropped, even while unwinding, see [crate::mdc::push_scope_fields]
+#[must_use = "the fields are detached again once the guard is dropped"]
+pub struct ScopeGuard {
+    depth: usize,
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        SCOPES.with(|scopes| scopes.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Attaches the fields to every msg logged within the block on the current thread, e.g.
+/// `scope_fields!(request_id = id => { info!("handling"); })`
+/// A field of the msg itself wins over a scoped field with the same key, an inner scope over an outer one,
+/// and a scoped field over a key of the [crate::mdc::mdc_put] context.
+/// The fields are detached when the block is left, even by a panic.
+#[macro_export]
+macro_rules! scope_fields {
+    ($($key: ident = $value: expr),+ $(,)? => $body: block) => {{
+        let _scope = $crate::mdc::push_scope_fields(&[$((stringify!($key), ($value).to_string())),+]);
+        $body
+    }};
+}
+
+#[doc(hidden)]
+/// Used by publish! to pass the msg on with the scoped fields and the context of the current thread appended to its own
+pub fn with_thread_fields<R>(msg: &LogMessage, f: impl FnOnce(&LogMessage) -> R) -> R {
+    // copied, so a sink may open a scope or put a key of its own while the msg is published
+    let scoped = SCOPES.with(|scopes| scopes.borrow().clone());
+    let context = MDC.with(|mdc| mdc.borrow().clone());
+    if scoped.is_empty() && context.is_empty() {
+        return f(msg);
+    }
+
+    let mut fields = msg.fields.to_vec();
+    for (i, (key, value)) in scoped.iter().enumerate() {
+        let shadowed = msg.fields.iter().any(|(field, _)| field == key)
+            || scoped[i + 1..].iter().any(|(inner, _)| inner == key);
+        if !shadowed {
+            fields.push((key, value));
+        }
+    }
+    for (key, value) in context.iter() {
+        if !fields.iter().any(|(field, _)| field == key) {
+            fields.push((key, value));
+        }
+    }
+    f(&LogMessage {
+        fields: &fields,
+        ..*msg
+    })
+}
+
+/// Calls f with the key-values of the current thread
+pub(crate) fn with_context<R>(f: impl FnOnce(&[(String, String)]) -> R) -> R {
+    MDC.with(|mdc| f(&mdc.borrow()))
+}
+
+/// Renders the context of the current thread as `key=value` pairs separated by spaces
+pub(crate) fn render(parsed: &mut String) {
+    with_context(|context| {
+        for (i, (key, value)) in context.iter().enumerate() {
+            if i > 0 {
+                parsed.push(' ');
+            }
+            parsed.push_str(key);
+            parsed.push('=');
+            parsed.push_str(value);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        info,
+        mdc::{mdc_clear, mdc_get, mdc_put, mdc_remove, push_context},
+        msg,
+        msg::LogSeverity,
+        publish, sink,
+        sink::{MemorySink, SinkDeclaration},
+    };
+
+    #[test]
+    fn mdc_works() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "mdc".to_string(),
+            severity: LogSeverity::Info,
+            module: module_path!().to_string(),
+            template: "%m [%X]".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        sink!(sink);
+
+        mdc_put("request_id", 42);
+        mdc_put("user", "alice");
+        info!("mdc: both");
+        mdc_put("request_id", 43);
+        info!("mdc: replaced");
+        assert_eq!(Some("43".to_string()), mdc_remove("request_id"));
+        info!("mdc: removed");
+        mdc_clear();
+        info!("mdc: cleared");
+        assert_eq!(None, mdc_get("user"));
+
+        // another thread has a context of its own
+        mdc_put("user", "bob");
+        std::thread::spawn(|| info!("mdc: other thread"))
+            .join()
+            .unwrap();
+        mdc_clear();
+
+        let lines: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with("mdc: "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec![
+                "mdc: both [request_id=42 user=alice]",
+                "mdc: replaced [request_id=43 user=alice]",
+                "mdc: removed [user=alice]",
+                "mdc: cleared []",
+                "mdc: other thread []",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn context_guard_works() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "context".to_string(),
+            severity: LogSeverity::Info,
+            module: module_path!().to_string(),
+            template: "%m [%x{request_id}]".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        sink!(sink);
+
+        info!("context: before");
+        {
+            let _request = push_context("request_id", 42);
+            info!("context: inside");
+            {
+                let _retry = push_context("request_id", "42-retry");
+                info!("context: nested");
+            }
+            info!("context: restored");
+        }
+        info!("context: after");
+        assert_eq!(None, mdc_get("request_id"));
+
+        let lines: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with("context: "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec![
+                "context: before []",
+                "context: inside [42]",
+                "context: nested [42-retry]",
+                "context: restored [42]",
+                "context: after []",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn mdc_fields_work() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "mdc fields".to_string(),
+            severity: LogSeverity::Info,
+            module: module_path!().to_string(),
+            template: "%m [%k]".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        sink!(sink);
+
+        mdc_put("request_id", 42);
+        mdc_put("user", "alice");
+        info!("mdc fields: merged"; status = 200);
+        info!("mdc fields: own"; user = "bob");
+        scope_fields!(request_id = "42-retry" => {
+            info!("mdc fields: scoped");
+        });
+        mdc_clear();
+        info!("mdc fields: cleared");
+
+        let lines: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with("mdc fields: "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec![
+                "mdc fields: merged [status=200 request_id=42 user=alice]",
+                "mdc fields: own [user=bob request_id=42]",
+                "mdc fields: scoped [request_id=42-retry user=alice]",
+                "mdc fields: cleared []",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn scope_fields_work() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "scope".to_string(),
+            severity: LogSeverity::Info,
+            module: module_path!().to_string(),
+            template: "%m [%k]".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        sink!(sink);
+
+        let id = 42;
+        info!("scope: before");
+        scope_fields!(request_id = id, user = "ada" => {
+            info!("scope: inside"; status = 200);
+            scope_fields!(request_id = "42-retry" => {
+                info!("scope: nested"; user = "bob");
+            });
+            info!("scope: restored");
+        });
+        let panicked = std::panic::catch_unwind(|| {
+            scope_fields!(request_id = id => {
+                info!("scope: panicking");
+                panic!("scope: failed");
+            })
+        });
+        assert!(panicked.is_err());
+        info!("scope: after");
+
+        let lines: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with("scope: "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec![
+                "scope: before []",
+                "scope: inside [status=200 request_id=42 user=ada]",
+                "scope: nested [user=bob request_id=42-retry]",
+                "scope: restored [request_id=42 user=ada]",
+                "scope: panicking [request_id=42]",
+                "scope: after []",
+            ],
+            lines
+        );
+    }
+}
@@ -1,10 +1,26 @@
-use std::time::SystemTime;
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Local, Offset, SecondsFormat, TimeZone, Utc};
 use contra::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
+use crate::{
+    sink::{Encoding, SinkDeclaration},
+    Error, Result,
+};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord, Hash)]
 pub enum LogSeverity {
+    Verbose = -3,
     Trace = -2,
     Debug = -1,
     Info = 0,
@@ -15,13 +31,55 @@ pub enum LogSeverity {
 
 impl ToString for LogSeverity {
     fn to_string(&self) -> String {
+        self.as_str().to_string()
+    }
+}
+
+impl std::str::FromStr for LogSeverity {
+    type Err = Error;
+
+    /// Parses the name of a severity case-insensitively, accepting common aliases like `warning` or `err`
+    fn from_str(severity: &str) -> Result<Self> {
+        match severity.trim().to_ascii_lowercase().as_str() {
+            "verbose" | "verb" => Ok(LogSeverity::Verbose),
+            "trace" => Ok(LogSeverity::Trace),
+            "debug" | "dbg" => Ok(LogSeverity::Debug),
+            "info" | "information" => Ok(LogSeverity::Info),
+            "warn" | "warning" => Ok(LogSeverity::Warn),
+            "error" | "err" => Ok(LogSeverity::Error),
+            "fatal" | "critical" | "crit" => Ok(LogSeverity::Fatal),
+            _ => Err(Error::Config(format!(
+                "unknown severity {}, expected one of verbose, trace, debug, info, warn, error or fatal",
+                severity
+            ))),
+        }
+    }
+}
+
+impl LogSeverity {
+    /// The label %i renders, padded to five characters
+    pub fn as_str(&self) -> &'static str {
         match self {
-            LogSeverity::Trace => "Trace".to_string(),
-            LogSeverity::Debug => "Debug".to_string(),
-            LogSeverity::Info => "Info ".to_string(),
-            LogSeverity::Warn => "Warn ".to_string(),
-            LogSeverity::Error => "Error".to_string(),
-            LogSeverity::Fatal => "Fatal".to_string(),
+            LogSeverity::Verbose => "Verb ",
+            LogSeverity::Trace => "Trace",
+            LogSeverity::Debug => "Debug",
+            LogSeverity::Info => "Info ",
+            LogSeverity::Warn => "Warn ",
+            LogSeverity::Error => "Error",
+            LogSeverity::Fatal => "Fatal",
+        }
+    }
+
+    /// The color used by the macro of this severity
+    pub fn color(&self) -> Color {
+        match self {
+            LogSeverity::Verbose => Color::DimGrey,
+            LogSeverity::Trace => Color::Grey,
+            LogSeverity::Debug => Color::Blue,
+            LogSeverity::Info => Color::Default,
+            LogSeverity::Warn => Color::Orange,
+            LogSeverity::Error => Color::Red,
+            LogSeverity::Fatal => Color::DarkRed,
         }
     }
 }
@@ -35,116 +93,1805 @@ pub struct LogMessage<'a> {
     pub(crate) msg: &'a str,
     pub(crate) severity: LogSeverity,
     pub(crate) color: Color,
+    pub(crate) style: Style,
+    pub(crate) tag: Option<&'a str>,
+    /// Structured key-values, e.g. from `info!("request done"; status = 200)`
+    pub(crate) fields: &'a [(&'a str, &'a str)],
+    pub(crate) uptime: Duration,
+}
+
+/// A [crate::msg::LogMessage] owning its strings, e.g. to hand it over to another thread
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct OwnedLogMessage {
+    pub(crate) time: SystemTime,
+    pub(crate) module: String,
+    pub(crate) file: String,
+    pub(crate) line: u32,
+    pub(crate) msg: String,
+    pub(crate) severity: LogSeverity,
+    pub(crate) color: Color,
+    pub(crate) style: Style,
+    pub(crate) tag: Option<String>,
+    pub(crate) fields: Vec<(String, String)>,
+    pub(crate) uptime: Duration,
+}
+
+impl OwnedLogMessage {
+    /// Borrows the msg again, e.g. to pass it on to a [crate::sink::Sink]
+    /// Takes a closure, as the borrowed fields have to live somewhere while f runs.
+    pub fn with_msg<R>(&self, f: impl FnOnce(&LogMessage) -> R) -> R {
+        let fields: Vec<(&str, &str)> = self
+            .fields
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        f(&LogMessage {
+            time: self.time,
+            module: &self.module,
+            file: &self.file,
+            line: self.line,
+            msg: &self.msg,
+            severity: self.severity,
+            color: self.color,
+            style: self.style,
+            tag: self.tag.as_deref(),
+            fields: &fields,
+            uptime: self.uptime,
+        })
+    }
+
+    /// Replaces all % patterns like [crate::msg::LogMessage::parse]
+    pub fn parse(&self, pattern: &str) -> String {
+        self.with_msg(|msg| msg.parse(pattern))
+    }
+
+    /// Parses the template of the [crate::sink::SinkDeclaration] like [crate::msg::LogMessage::render]
+    pub fn render(&self, decl: &SinkDeclaration) -> String {
+        self.with_msg(|msg| msg.render(decl))
+    }
+}
+
+/// Renders bytes as standard base64 with padding, e.g. a hash or a packet as a structured field
+/// `info!("packet received"; payload = Base64(&packet))` renders `payload=3q2+7w==` in %k and a base64 string in JSON.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Base64<'a>(pub &'a [u8]);
+
+impl std::fmt::Display for Base64<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut encoded = String::with_capacity(self.0.len().div_ceil(3) * 4);
+        for chunk in self.0.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+                bits | (*byte as u32) << (16 - 8 * i)
+            });
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        f.write_str(&encoded)
+    }
+}
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Returns the monotonic time elapsed since logtra was first used
+/// Unlike the timestamp of a [crate::msg::LogMessage] this does not depend on a real-time clock
+pub fn uptime() -> Duration {
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// Renders how long ago the time was, e.g. "2s ago" or "5m ago", or "in 3h" for a time in the future
+/// The current time is read on every call, so the result is only meaningful for live rendering.
+pub fn humanize_ago(time: SystemTime) -> String {
+    humanize_ago_at(time, SystemTime::now())
+}
+
+/// Like [crate::msg::humanize_ago], but relative to the given current time
+pub fn humanize_ago_at(time: SystemTime, now: SystemTime) -> String {
+    let (elapsed, future) = match now.duration_since(time) {
+        Ok(elapsed) => (elapsed, false),
+        Err(err) => (err.duration(), true),
+    };
+
+    let secs = elapsed.as_secs();
+    let amount = match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    };
+    match future {
+        true => format!("in {}", amount),
+        false => format!("{} ago", amount),
+    }
+}
+
+thread_local! {
+    static THREAD_ID_OVERRIDE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Overrides what %c renders on the current thread, or restores the real thread id with `None`
+/// Intended for golden tests, which can not rely on the nondeterministic [std::thread::ThreadId]
+pub fn override_thread_id(id: Option<&str>) {
+    THREAD_ID_OVERRIDE.with(|thread_id| *thread_id.borrow_mut() = id.map(str::to_string));
+}
+
+thread_local! {
+    static LOCAL_OFFSET_OVERRIDE: Cell<Option<FixedOffset>> = const { Cell::new(None) };
+}
+
+/// Overrides the local timezone of %L and %z on the current thread, or restores the one of the system with `None`
+/// Intended for tests, which can not rely on the timezone of the machine they run on
+pub fn override_local_offset(offset: Option<FixedOffset>) {
+    LOCAL_OFFSET_OVERRIDE.with(|override_| override_.set(offset));
+}
+
+/// Returns the offset of the local timezone at the given time, honoring [crate::msg::override_local_offset]
+fn local_offset(time: &DateTime<Utc>) -> FixedOffset {
+    LOCAL_OFFSET_OVERRIDE
+        .with(Cell::get)
+        .unwrap_or_else(|| Local.offset_from_utc_datetime(&time.naive_utc()).fix())
+}
+
+/// Renders the id of the current thread without the `ThreadId(...)` around it, honoring [crate::msg::override_thread_id]
+fn push_numeric_thread_id(parsed: &mut String) {
+    THREAD_ID_OVERRIDE.with(|thread_id| match thread_id.borrow().as_deref() {
+        Some(thread_id) => parsed.push_str(thread_id),
+        // ThreadId::as_u64 is unstable, so the number is taken from the debug output
+        None => parsed.extend(
+            format!("{:?}", std::thread::current().id())
+                .chars()
+                .filter(char::is_ascii_digit),
+        ),
+    });
+}
+
+/// Every character which may follow a % in a template, see [crate::msg::LogMessage::parse]
+const PATTERNS: &str = "tTLeEzUAcCnpiImflsgXkFx[]%";
+
+/// The patterns which take an argument in braces, e.g. `%x{request_id}`
+const ARGUMENT_PATTERNS: &str = "xtF";
+
+/// The patterns which can not do without their argument
+const REQUIRED_ARGUMENT_PATTERNS: &str = "x";
+
+/// The numeric patterns which may be padded to a width, e.g. `%05l`
+const PADDED_PATTERNS: &str = "leEp";
+
+/// The widest padding of a numeric pattern, wider than any number it renders
+const MAX_PADDING: usize = 32;
+
+/// Checks that every % in the template is followed by a known pattern
+pub fn validate_template(template: &str) -> Result<()> {
+    let mut escaped = false;
+    let mut replace = false;
+    let mut width: Option<usize> = None;
+
+    let mut chars = template.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        if replace {
+            if let Some(digit) = c.to_digit(10) {
+                width = width
+                    .unwrap_or_default()
+                    .checked_mul(10)
+                    .and_then(|width| width.checked_add(digit as usize))
+                    .filter(|width| *width <= MAX_PADDING);
+                if width.is_none() {
+                    return Err(Error::TemplateParse(format!(
+                        "the width at {} in \"{}\" exceeds {}",
+                        i, template, MAX_PADDING
+                    )));
+                }
+                continue;
+            }
+            if width.is_some() && !PADDED_PATTERNS.contains(c) {
+                return Err(Error::TemplateParse(format!(
+                    "%{} at {} in \"{}\" can not be padded, only %l, %e, %E and %p can",
+                    c, i, template
+                )));
+            }
+            if c == 'Z' {
+                return Err(Error::TemplateParse(format!(
+                    "%Z at {} in \"{}\" is not supported, zone abbreviations need a timezone database, use %z for the offset",
+                    i, template
+                )));
+            }
+            if !PATTERNS.contains(c) {
+                return Err(Error::TemplateParse(format!(
+                    "unknown pattern %{} at {} in \"{}\"",
+                    c, i, template
+                )));
+            }
+            if ARGUMENT_PATTERNS.contains(c) && chars.next_if(|(_, c)| *c == '{').is_some() {
+                let mut argument = String::new();
+                let mut terminated = false;
+                for (_, c) in chars.by_ref() {
+                    if c == '}' {
+                        terminated = true;
+                        break;
+                    }
+                    argument.push(c);
+                }
+                if !terminated {
+                    return Err(Error::TemplateParse(format!(
+                        "unterminated argument of %{} at {} in \"{}\"",
+                        c, i, template
+                    )));
+                }
+                if c == 't'
+                    && chrono::format::StrftimeItems::new(&argument)
+                        .any(|item| item == chrono::format::Item::Error)
+                {
+                    return Err(Error::TemplateParse(format!(
+                        "%t{{{}}} at {} in \"{}\" contains an unknown strftime pattern",
+                        argument, i, template
+                    )));
+                }
+            } else if REQUIRED_ARGUMENT_PATTERNS.contains(c) {
+                return Err(Error::TemplateParse(format!(
+                    "%{} at {} in \"{}\" expects an argument in braces",
+                    c, i, template
+                )));
+            }
+            replace = false;
+            width = None;
+            continue;
+        }
+
+        match c {
+            '\\' => escaped = true,
+            '%' => replace = true,
+            _ => (),
+        }
+    }
+
+    if replace {
+        return Err(Error::TemplateParse(format!(
+            "unterminated % at the end of \"{}\"",
+            template
+        )));
+    }
+    Ok(())
+}
+
+/// The width of a numeric % pattern, e.g. `%05l` or `%5l`
+/// Values wider than the width are never truncated
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+struct Padding {
+    width: usize,
+    zeroes: bool,
+}
+
+impl Padding {
+    fn push(&self, parsed: &mut String, value: impl ToString) {
+        let value = value.to_string();
+        let fill = if self.zeroes { '0' } else { ' ' };
+        for _ in value.len()..self.width {
+            parsed.push(fill);
+        }
+        parsed.push_str(&value);
+    }
+}
+
+/// A part of a compiled [crate::msg::Template]
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Pattern(char, Padding),
+    /// A pattern with its argument in braces, e.g. `%x{request_id}`
+    Argument(char, String),
+}
+
+/// A template split into its literal parts and % patterns, so rendering does not have to scan it again
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+struct Template {
+    segments: Vec<Segment>,
+}
+
+/// How many distinct templates are kept compiled per thread, further templates are compiled on every use
+const INTERNED_TEMPLATES: usize = 64;
+
+thread_local! {
+    static TEMPLATES: RefCell<HashMap<String, Rc<Template>>> = RefCell::new(HashMap::new());
+}
+
+impl Template {
+    fn compile(pattern: &str) -> Self {
+        let mut template = Template::default();
+        let mut literal = String::new();
+
+        let mut escaped = false;
+        let mut replace = false;
+        let mut padding = Padding::default();
+
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            if escaped {
+                escaped = false;
+                literal.push(c);
+                continue;
+            }
+
+            if replace {
+                if let Some(digit) = c.to_digit(10) {
+                    padding.zeroes |= digit == 0 && padding.width == 0;
+                    // validate_template rejects wider paddings, unvalidated ones are capped
+                    padding.width = padding
+                        .width
+                        .saturating_mul(10)
+                        .saturating_add(digit as usize)
+                        .min(MAX_PADDING);
+                    continue;
+                }
+                replace = false;
+                if c == '%' {
+                    literal.push('%');
+                    padding = Padding::default();
+                    continue;
+                }
+                if !literal.is_empty() {
+                    template
+                        .segments
+                        .push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                match ARGUMENT_PATTERNS.contains(c) && chars.next_if_eq(&'{').is_some() {
+                    true => {
+                        let argument = chars.by_ref().take_while(|c| *c != '}').collect();
+                        template.segments.push(Segment::Argument(c, argument));
+                    }
+                    false => template.segments.push(Segment::Pattern(c, padding)),
+                }
+                padding = Padding::default();
+                continue;
+            }
+
+            match c {
+                '\\' => escaped = true,
+                '%' => replace = true,
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            template.segments.push(Segment::Literal(literal));
+        }
+        template
+    }
+
+    /// Calls f with the compiled pattern, compiling it only on the first use on the current thread
+    fn interned<R>(pattern: &str, f: impl FnOnce(&Template) -> R) -> R {
+        let template = TEMPLATES.with(|templates| {
+            let mut templates = templates.borrow_mut();
+            if let Some(template) = templates.get(pattern) {
+                return Some(template.clone());
+            }
+            if templates.len() >= INTERNED_TEMPLATES {
+                return None;
+            }
+            let template = Rc::new(Template::compile(pattern));
+            templates.insert(pattern.to_string(), template.clone());
+            Some(template)
+        });
+
+        match template {
+            Some(template) => f(&template),
+            None => f(&Template::compile(pattern)),
+        }
+    }
+}
+
+/// A template compiled once for a sink, which renders it for every msg without looking it up again
+/// Keeps the pattern it was compiled from, so a sink can recompile it after its declaration was changed.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct CompiledTemplate {
+    pattern: String,
+    template: Template,
+}
+
+impl CompiledTemplate {
+    pub fn new(pattern: &str) -> Self {
+        CompiledTemplate {
+            pattern: pattern.to_string(),
+            template: Template::compile(pattern),
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Recompiles the template if the pattern differs from the one it was compiled from
+    pub fn update(&mut self, pattern: &str) {
+        if self.pattern != pattern {
+            *self = CompiledTemplate::new(pattern);
+        }
+    }
+}
+
+/// Escapes a string to be embedded in a JSON string literal
+pub(crate) fn json_escape(out: &mut String, value: &str) {
+    use std::fmt::Write as _;
+
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+/// Appends `,"name":{"key":"value",...}` to a JSON object under construction
+fn push_json_object<'a>(
+    json: &mut String,
+    name: &str,
+    entries: impl Iterator<Item = (&'a str, &'a str)>,
+) {
+    json.push_str(",\"");
+    json.push_str(name);
+    json.push_str("\":{");
+    for (i, (key, value)) in entries.enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push('"');
+        json_escape(json, key);
+        json.push_str("\":\"");
+        json_escape(json, value);
+        json.push('"');
+    }
+    json.push('}');
 }
 
 impl<'a> LogMessage<'a> {
+    /// Copies the borrowed strings, so the msg can outlive them
+    pub fn to_owned(&self) -> OwnedLogMessage {
+        OwnedLogMessage {
+            time: self.time,
+            module: self.module.to_string(),
+            file: self.file.to_string(),
+            line: self.line,
+            msg: self.msg.to_string(),
+            severity: self.severity,
+            color: self.color,
+            style: self.style,
+            tag: self.tag.map(str::to_string),
+            fields: self
+                .fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            uptime: self.uptime,
+        }
+    }
+
     /// Replaces all % patterns with the appropriate content
-    /// %t = UTC timestamp
-    /// %c = current thread id
+    /// %t = UTC timestamp as RFC3339, or formatted by the strftime pattern in braces, e.g. %t{%H:%M:%S}
+    /// %T = UTC timestamp with milliseconds, e.g. 2024-01-02T03:04:05.123Z
+    /// %L = local timestamp as RFC3339, e.g. 2024-01-02T05:04:05+02:00
+    /// %e = seconds since the Unix epoch
+    /// %E = milliseconds since the Unix epoch
+    /// %z = local UTC offset, e.g. +02:00
+    /// %U = uptime as seconds.millis
+    /// %A = age of the msg when rendered, e.g. 5m ago, see [crate::msg::humanize_ago]
+    /// %c = current thread id, e.g. ThreadId(2)
+    /// %C = current thread id as number, e.g. 2
+    /// %n = name of the current thread, or its id as number if it is unnamed
+    /// %p = id of the current process
     /// %i = log severity
+    /// %I = minimum severity of the rendering sink, empty outside of a sink
     /// %m = log message
     /// %f = file
     /// %l = line
     /// %s = module
+    /// %g = tag
+    /// %X = mapped diagnostic context of the logging thread, see [crate::mdc::mdc_put]
+    /// %k = structured fields of the msg as key=value pairs, see [crate::msg!]
+    /// %F = structured fields in logfmt, quoting values with spaces, quotes or `=`, separated by spaces or the separator in braces, e.g. %F{, }
+    /// %x{key} = value of the key in the diagnostic context of the logging thread, empty if it is not set
     /// %[ = color start
     /// %] = color stop
+    /// %% = a literal %
+    ///
+    /// Numeric patterns accept a width of up to 32 between the % and the pattern character,
+    /// padded with spaces (%5l) or zeroes (%05l). [crate::msg::validate_template] rejects a width on any other pattern.
+    ///
+    /// %f and %l render empty if the [crate::sink::SinkDeclaration] strips the source location,
+    /// %[ and %] if it strips colors or colors are disabled, see [crate::msg::colors_disabled].
     #[inline]
-    fn replace(&self, c: char, mut parsed: String) -> String {
+    fn replace(
+        &self,
+        c: char,
+        padding: Padding,
+        decl: Option<&SinkDeclaration>,
+        mut parsed: String,
+    ) -> String {
+        let strip_location = decl.is_some_and(|decl| decl.strip_location);
+        let strip_colors = decl.is_some_and(|decl| decl.strip_colors);
         match c {
-            '[' => parsed.push_str(&self.color.ansi()),
+            'f' | 'l' if strip_location => (),
+            '[' | ']' if strip_colors || colors_disabled() => (),
+            '[' => match decl.and_then(|decl| decl.colors.get(&self.severity)) {
+                Some(color) => parsed.push_str(&color.ansi()),
+                None => match palette_ansi(self.severity) {
+                    Some(ansi) => parsed.push_str(&ansi),
+                    None => parsed.push_str(&self.color.styled(self.style)),
+                },
+            },
             ']' => parsed.push_str(&Color::Default.ansi()),
             's' => parsed.push_str(self.module),
+            'g' => parsed.push_str(self.tag.unwrap_or_default()),
+            'X' => crate::mdc::render(&mut parsed),
+            'k' => {
+                for (i, (key, value)) in self.fields.iter().enumerate() {
+                    if i > 0 {
+                        parsed.push(' ');
+                    }
+                    parsed.push_str(key);
+                    parsed.push('=');
+                    parsed.push_str(value);
+                }
+            }
+            'F' => self.push_logfmt(" ", &mut parsed),
             'f' => parsed.push_str(self.file),
-            'l' => parsed.push_str(&self.line.to_string()),
+            'l' => padding.push(&mut parsed, self.line),
             'm' => parsed.push_str(self.msg),
-            'i' => parsed.push_str(&self.severity.to_string()),
+            'i' => parsed.push_str(self.severity.as_str()),
+            'I' => {
+                if let Some(decl) = decl {
+                    parsed.push_str(decl.severity.as_str());
+                }
+            }
             't' => parsed.push_str(&DateTime::<Utc>::from(self.time).to_rfc3339()),
-            'c' => parsed.push_str(&format!("{:?}", std::thread::current().id())),
+            'T' => parsed.push_str(
+                &DateTime::<Utc>::from(self.time).to_rfc3339_opts(SecondsFormat::Millis, true),
+            ),
+            'L' => {
+                let time = DateTime::<Utc>::from(self.time);
+                parsed.push_str(&time.with_timezone(&local_offset(&time)).to_rfc3339())
+            }
+            'e' => padding.push(&mut parsed, DateTime::<Utc>::from(self.time).timestamp()),
+            'E' => padding.push(
+                &mut parsed,
+                DateTime::<Utc>::from(self.time).timestamp_millis(),
+            ),
+            'z' => parsed.push_str(&local_offset(&self.time.into()).to_string()),
+            'A' => parsed.push_str(&humanize_ago(self.time)),
+            'U' => parsed.push_str(&format!(
+                "{}.{:03}",
+                self.uptime.as_secs(),
+                self.uptime.subsec_millis()
+            )),
+            'c' => THREAD_ID_OVERRIDE.with(|thread_id| match thread_id.borrow().as_deref() {
+                Some(thread_id) => parsed.push_str(thread_id),
+                None => parsed.push_str(&format!("{:?}", std::thread::current().id())),
+            }),
+            'C' => push_numeric_thread_id(&mut parsed),
+            'p' => padding.push(&mut parsed, std::process::id()),
+            'n' => match std::thread::current().name() {
+                Some(name) => parsed.push_str(name),
+                None => push_numeric_thread_id(&mut parsed),
+            },
             _ => (),
         };
         parsed
     }
 
-    pub fn parse(&self, pattern: &str) -> String {
-        let mut parsed = String::new();
-
-        let mut escaped = false;
-        let mut replace = false;
+    /// Replaces a pattern with an argument, see [crate::msg::LogMessage::replace]
+    #[inline]
+    fn replace_argument(&self, c: char, argument: &str, parsed: &mut String) {
+        use std::fmt::Write as _;
 
-        for c in pattern.chars() {
-            if escaped {
-                escaped = false;
-                parsed.push(c);
-                continue;
+        match c {
+            'x' => {
+                if let Some(value) = crate::mdc::mdc_get(argument) {
+                    parsed.push_str(&value);
+                }
             }
-
-            if replace {
-                replace = false;
-                parsed = self.replace(c, parsed);
-                continue;
+            'F' => self.push_logfmt(argument, parsed),
+            // an unknown strftime pattern fails the write instead of panicking, validate_template rejects it upfront
+            't' => {
+                let _ = write!(
+                    parsed,
+                    "{}",
+                    DateTime::<Utc>::from(self.time).format(argument)
+                );
             }
+            _ => (),
+        }
+    }
 
-            if c == '\\' {
-                escaped = true;
-                continue;
+    /// Renders the fields as logfmt `key=value` pairs, quoting values which contain spaces, quotes or `=` and empty ones
+    fn push_logfmt(&self, separator: &str, parsed: &mut String) {
+        for (i, (key, value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                parsed.push_str(separator);
             }
-
-            if c == '%' {
-                replace = true;
+            parsed.push_str(key);
+            parsed.push('=');
+            if !value.is_empty() && !value.contains([' ', '"', '=', '\\']) {
+                parsed.push_str(value);
                 continue;
             }
+            parsed.push('"');
+            for c in value.chars() {
+                if c == '"' || c == '\\' {
+                    parsed.push('\\');
+                }
+                parsed.push(c);
+            }
+            parsed.push('"');
+        }
+    }
 
-            parsed.push(c);
+    /// Estimates the length of the parsed pattern to avoid reallocating while parsing
+    /// Reserves room for the message, module and file once, plus a timestamp sized slack
+    #[inline]
+    fn estimate_capacity(&self, pattern: &str) -> usize {
+        const SLACK: usize = 64;
+        pattern.len() + self.msg.len() + self.module.len() + self.file.len() + SLACK
+    }
+
+    pub fn parse(&self, pattern: &str) -> String {
+        self.parse_with(pattern, None)
+    }
+
+    /// Appends the pattern replaced like [crate::msg::LogMessage::parse] to out, which keeps its content and capacity
+    pub fn parse_into(&self, pattern: &str, out: &mut String) {
+        self.parse_into_with(pattern, None, out)
+    }
+
+    /// Parses the template of the [crate::sink::SinkDeclaration], honoring its rendering options and encoding
+    pub fn render(&self, decl: &SinkDeclaration) -> String {
+        decl.encoding
+            .encode(self.parse_with(&decl.template, Some(decl)))
+    }
+
+    /// Appends the msg rendered like [crate::msg::LogMessage::render] to out, which keeps its content and capacity
+    pub fn render_into(&self, decl: &SinkDeclaration, out: &mut String) {
+        Template::interned(&decl.template, |template| {
+            self.render_template_into(template, decl, out)
+        })
+    }
+
+    /// Replaces all % patterns of the compiled template like [crate::msg::LogMessage::parse]
+    pub fn parse_compiled(&self, template: &CompiledTemplate) -> String {
+        let mut parsed = String::with_capacity(self.estimate_capacity(template.pattern()));
+        self.render_template(&template.template, None, &mut parsed);
+        parsed
+    }
+
+    /// Appends the msg rendered like [crate::msg::LogMessage::render_into], using the compiled template instead of the one of the declaration
+    pub fn render_compiled_into(
+        &self,
+        template: &CompiledTemplate,
+        decl: &SinkDeclaration,
+        out: &mut String,
+    ) {
+        self.render_template_into(&template.template, decl, out)
+    }
+
+    /// Renders the msg as a single line JSON object, honoring the rendering options and encoding of the [crate::sink::SinkDeclaration]
+    /// Contains `timestamp` (RFC3339), `level`, `module`, `file`, `line` and `message`,
+    /// plus `tag` and the structured fields as `fields` object if present.
+    /// Ignores the template.
+    pub fn render_json(&self, decl: &SinkDeclaration) -> String {
+        let mut json = String::with_capacity(self.estimate_capacity(""));
+        json.push_str("{\"timestamp\":\"");
+        json.push_str(&DateTime::<Utc>::from(self.time).to_rfc3339());
+        json.push_str("\",\"level\":\"");
+        json.push_str(self.severity.as_str().trim_end());
+        json.push_str("\",\"module\":\"");
+        json_escape(&mut json, self.module);
+        json.push('"');
+        if !decl.strip_location {
+            json.push_str(",\"file\":\"");
+            json_escape(&mut json, self.file);
+            json.push_str("\",\"line\":");
+            json.push_str(&self.line.to_string());
+        }
+        json.push_str(",\"message\":\"");
+        json_escape(&mut json, self.msg);
+        json.push('"');
+        if let Some(tag) = self.tag {
+            json.push_str(",\"tag\":\"");
+            json_escape(&mut json, tag);
+            json.push('"');
         }
+        if !self.fields.is_empty() {
+            push_json_object(
+                &mut json,
+                "fields",
+                self.fields.iter().map(|(key, value)| (*key, *value)),
+            );
+        }
+        json.push('}');
+        decl.encoding.encode(json)
+    }
 
+    fn parse_with(&self, pattern: &str, decl: Option<&SinkDeclaration>) -> String {
+        let mut parsed = String::new();
+        self.parse_into_with(pattern, decl, &mut parsed);
         parsed
     }
+
+    fn parse_into_with(&self, pattern: &str, decl: Option<&SinkDeclaration>, out: &mut String) {
+        out.reserve(self.estimate_capacity(pattern));
+        Template::interned(pattern, |template| {
+            self.render_template(template, decl, out)
+        })
+    }
+
+    fn render_template_into(&self, template: &Template, decl: &SinkDeclaration, out: &mut String) {
+        let start = out.len();
+        out.reserve(self.estimate_capacity(&decl.template));
+        self.render_template(template, Some(decl), out);
+        if decl.encoding != Encoding::Passthrough {
+            let rendered = out.split_off(start);
+            out.push_str(&decl.encoding.encode(rendered));
+        }
+    }
+
+    fn render_template(
+        &self,
+        template: &Template,
+        decl: Option<&SinkDeclaration>,
+        parsed: &mut String,
+    ) {
+        for segment in template.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => parsed.push_str(literal),
+                Segment::Pattern(c, padding) => {
+                    *parsed = self.replace(*c, *padding, decl, std::mem::take(parsed))
+                }
+                Segment::Argument(c, argument) => self.replace_argument(*c, argument, parsed),
+            }
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
 pub enum Color {
     Default,
+    DimGrey,
     Grey,
     Blue,
     White,
     Orange,
     Red,
     DarkRed,
+    Green,
+    Yellow,
+    Cyan,
+    Magenta,
+    /// A 24-bit truecolor, for terminals which support it
+    Rgb(u8, u8, u8),
+}
+
+impl std::str::FromStr for Color {
+    type Err = Error;
+
+    /// Parses the name of a color case-insensitively, e.g. `red` or `dark_red`, or a truecolor as `#rrggbb`
+    fn from_str(color: &str) -> Result<Self> {
+        let color = color.trim();
+        if let Some(hex) = color.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+                _ => Err(Error::Config(format!(
+                    "{} is not a hex color, expected #rrggbb",
+                    color
+                ))),
+            };
+        }
+
+        let name = color
+            .chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "default" => Ok(Color::Default),
+            "dimgrey" | "dimgray" => Ok(Color::DimGrey),
+            "grey" | "gray" => Ok(Color::Grey),
+            "blue" => Ok(Color::Blue),
+            "white" => Ok(Color::White),
+            "orange" => Ok(Color::Orange),
+            "red" => Ok(Color::Red),
+            "darkred" => Ok(Color::DarkRed),
+            "green" => Ok(Color::Green),
+            "yellow" => Ok(Color::Yellow),
+            "cyan" => Ok(Color::Cyan),
+            "magenta" => Ok(Color::Magenta),
+            _ => Err(Error::Config(format!(
+                "unknown color {}, expected a name like red or a hex color like #ff8000",
+                color
+            ))),
+        }
+    }
+}
+
+/// Text styles %[ starts together with the color of a msg, combined with `|`, e.g. `Style::BOLD | Style::UNDERLINE`
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Hash)]
+pub struct Style(u8);
+
+impl Style {
+    pub const NONE: Style = Style(0);
+    pub const BOLD: Style = Style(1);
+    pub const DIM: Style = Style(1 << 1);
+    pub const ITALIC: Style = Style(1 << 2);
+    pub const UNDERLINE: Style = Style(1 << 3);
+
+    /// Returns whether every style of other is set
+    pub fn contains(&self, other: Style) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the SGR parameters of the style, e.g. `1;4`, empty without a style
+    fn sgr(&self) -> String {
+        [
+            (Style::BOLD, "1"),
+            (Style::DIM, "2"),
+            (Style::ITALIC, "3"),
+            (Style::UNDERLINE, "4"),
+        ]
+        .iter()
+        .filter(|(style, _)| self.contains(*style))
+        .map(|(_, code)| *code)
+        .collect::<Vec<_>>()
+        .join(";")
+    }
+}
+
+impl std::ops::BitOr for Style {
+    type Output = Style;
+
+    fn bitor(self, rhs: Style) -> Style {
+        Style(self.0 | rhs.0)
+    }
+}
+
+/// Whether the NO_COLOR environment variable was set to anything non-empty when logtra first rendered a color
+static NO_COLOR: OnceLock<bool> = OnceLock::new();
+
+thread_local! {
+    static NO_COLOR_OVERRIDE: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Follows the NO_COLOR convention, see <https://no-color.org>
+fn no_color_requested(value: Option<&std::ffi::OsStr>) -> bool {
+    value.is_some_and(|value| !value.is_empty())
+}
+
+/// Returns whether colors are disabled, by NO_COLOR or [crate::msg::override_no_color]
+/// The environment is read only once, so changing NO_COLOR later has no effect.
+pub fn colors_disabled() -> bool {
+    NO_COLOR_OVERRIDE.with(Cell::get).unwrap_or_else(|| {
+        *NO_COLOR.get_or_init(|| no_color_requested(std::env::var_os("NO_COLOR").as_deref()))
+    })
+}
+
+/// Overrides whether colors are disabled on the current thread, or restores the NO_COLOR environment variable with `None`
+/// Intended for tests, which can not change the environment of the whole process
+pub fn override_no_color(no_color: Option<bool>) {
+    NO_COLOR_OVERRIDE.with(|override_| override_.set(no_color));
 }
 
 impl Color {
-    pub fn ansi(&self) -> String {
+    /// Returns the escape sequence starting the color, empty if colors are disabled, see [crate::msg::colors_disabled]
+    /// Only [crate::msg::Color::Rgb] allocates, the named colors are static.
+    pub fn ansi(&self) -> Cow<'static, str> {
+        if colors_disabled() {
+            return Cow::Borrowed("");
+        }
         match self {
-            Color::Default => "\x1b[0m".to_string(),
-            Color::Grey => "\x1b[90m".to_string(),
-            Color::Blue => "\x1b[34m".to_string(),
-            Color::White => "\x1b[97m".to_string(),
-            Color::Orange => "\x1b[33m".to_string(),
-            Color::Red => "\x1b[31m".to_string(),
-            Color::DarkRed => "\x1b[91m".to_string(),
+            Color::Default => Cow::Borrowed("\x1b[0m"),
+            Color::DimGrey => Cow::Borrowed("\x1b[2;90m"),
+            Color::Grey => Cow::Borrowed("\x1b[90m"),
+            Color::Blue => Cow::Borrowed("\x1b[34m"),
+            Color::White => Cow::Borrowed("\x1b[97m"),
+            Color::Orange => Cow::Borrowed("\x1b[33m"),
+            Color::Red => Cow::Borrowed("\x1b[31m"),
+            Color::DarkRed => Cow::Borrowed("\x1b[91m"),
+            Color::Green => Cow::Borrowed("\x1b[32m"),
+            Color::Yellow => Cow::Borrowed("\x1b[33m"),
+            Color::Cyan => Cow::Borrowed("\x1b[36m"),
+            Color::Magenta => Cow::Borrowed("\x1b[35m"),
+            Color::Rgb(r, g, b) => Cow::Owned(format!("\x1b[38;2;{};{};{}m", r, g, b)),
+        }
+    }
+
+    /// Returns the escape sequence starting the color in the style, e.g. `\x1b[1;31m` for bold red
+    pub fn styled(&self, style: Style) -> Cow<'static, str> {
+        let ansi = self.ansi();
+        if style == Style::NONE || ansi.is_empty() {
+            return ansi;
         }
+        // the parameters between "\x1b[" and "m"
+        let color = &ansi[2..ansi.len() - 1];
+        Cow::Owned(match self {
+            // resetting after the style would undo it
+            Color::Default => format!("\x1b[{};{}m", color, style.sgr()),
+            _ => format!("\x1b[{};{}m", style.sgr(), color),
+        })
+    }
+}
+
+/// Returns whether the code is a list of SGR parameters, e.g. `1;31`
+pub(crate) fn is_sgr(code: &str) -> bool {
+    !code.is_empty()
+        && code
+            .split(';')
+            .all(|param| !param.is_empty() && param.parse::<u8>().is_ok())
+}
+
+/// Overrides the color %[ starts per severity, instead of using the color of the msg
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Palette {
+    colors: HashMap<LogSeverity, String>,
+}
+
+impl Palette {
+    /// Parses one `<severity> = <SGR parameters>` mapping per line, e.g. `Error = 1;31`
+    /// Empty lines and lines starting with # are ignored, severities without a mapping keep the color of the msg.
+    pub fn from_config(config: &str) -> Result<Self> {
+        let mut palette = Palette::default();
+
+        for (i, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (severity, code) = line.split_once('=').ok_or_else(|| {
+                Error::Config(format!("expected <severity> = <code> in line {}", i + 1))
+            })?;
+            let severity: LogSeverity = severity.parse().map_err(|_| {
+                Error::Config(format!(
+                    "unknown severity {} in line {}",
+                    severity.trim(),
+                    i + 1
+                ))
+            })?;
+            let code = code.trim();
+            if !is_sgr(code) {
+                return Err(Error::Config(format!(
+                    "{} in line {} is not a list of SGR parameters, e.g. 1;31",
+                    code,
+                    i + 1
+                )));
+            }
+
+            palette.colors.insert(severity, format!("\x1b[{}m", code));
+        }
+
+        Ok(palette)
+    }
+}
+
+static PALETTE: RwLock<Option<Palette>> = RwLock::new(None);
+/// Avoids locking the palette while rendering as long as none is set
+static PALETTE_SET: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static PALETTE_OVERRIDE: RefCell<Option<Palette>> = const { RefCell::new(None) };
+}
+
+/// Sets the [crate::msg::Palette] used by %[ of every sink, or restores the colors of the msgs with `None`
+pub fn set_palette(palette: Option<Palette>) {
+    let mut current = PALETTE.write().unwrap_or_else(|err| err.into_inner());
+    PALETTE_SET.store(palette.is_some(), Ordering::Relaxed);
+    *current = palette;
+}
+
+/// Overrides the [crate::msg::Palette] on the current thread, or restores the one of [crate::msg::set_palette] with `None`
+/// Intended for tests, which would change the colors of every other thread with [crate::msg::set_palette]
+pub fn override_palette(palette: Option<Palette>) {
+    PALETTE_OVERRIDE.with(|override_| *override_.borrow_mut() = palette);
+}
+
+fn palette_ansi(severity: LogSeverity) -> Option<String> {
+    let overridden = PALETTE_OVERRIDE.with(|palette| {
+        let palette = palette.borrow();
+        palette
+            .as_ref()
+            .map(|palette| palette.colors.get(&severity).cloned())
+    });
+    if let Some(ansi) = overridden {
+        return ansi;
     }
+    if !PALETTE_SET.load(Ordering::Relaxed) {
+        return None;
+    }
+    let palette = PALETTE.read().unwrap_or_else(|err| err.into_inner());
+    palette.as_ref()?.colors.get(&severity).cloned()
 }
 
 #[cfg(test)]
 mod test {
-    use chrono::{DateTime, Utc};
+    use std::time::{Duration, Instant, SystemTime};
 
-    use crate::msg::{Color, LogMessage};
+    use chrono::{DateTime, FixedOffset, Utc};
 
-    #[test]
-    fn log_message_parsing_works() {
-        let msg = LogMessage {
+    use crate::{
+        msg::{
+            humanize_ago_at, override_local_offset, override_no_color, override_palette,
+            override_thread_id, validate_template, Base64, Color, CompiledTemplate, LogMessage,
+            LogSeverity, OwnedLogMessage, Padding, Palette, Segment, Style, Template, MAX_PADDING,
+        },
+        sink::{Encoding, SinkDeclaration},
+        Error,
+    };
+
+    /// A msg logged at the epoch from lib.rs:12, tests override other fields with struct update syntax
+    fn message(msg: &str, severity: LogSeverity) -> LogMessage<'_> {
+        LogMessage {
             time: DateTime::<Utc>::default().into(),
             module: "logtra",
             file: "lib.rs",
             line: 12,
-            msg: "Hello world!",
-            severity: crate::msg::LogSeverity::Info,
+            msg,
+            severity,
             color: Color::Red,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn owned_log_message_works() {
+        let owned: OwnedLogMessage = {
+            let module = String::from("logtra::owned");
+            let file = String::from("lib.rs");
+            let text = format!("{} {}", "outlives", "its strings");
+            let tag = String::from("audit");
+            let msg = LogMessage {
+                module: &module,
+                file: &file,
+                tag: Some(&tag),
+                fields: &[("user", "ada")],
+                uptime: Duration::from_millis(1500),
+                ..message(&text, LogSeverity::Warn)
+            };
+            msg.to_owned()
+        };
+
+        let template = "[%t][%i][%s][%f:%03l][%g][%U]: %m [%k]";
+        let expected = "[1970-01-01T00:00:00+00:00][Warn ][logtra::owned][lib.rs:012][audit][1.500]: outlives its strings [user=ada]";
+        assert_eq!(expected, owned.parse(template));
+        assert_eq!(
+            owned.with_msg(|msg| msg.parse(template)),
+            owned.parse(template)
+        );
+        let decl = SinkDeclaration {
+            template: template.to_string(),
+            strip_location: true,
+            ..Default::default()
         };
+        assert_eq!(owned.with_msg(|msg| msg.render(&decl)), owned.render(&decl));
+        assert_eq!(owned, owned.with_msg(|msg| msg.to_owned()));
+    }
+
+    #[test]
+    fn log_message_parsing_works() {
+        let msg = message("Hello world!", LogSeverity::Info);
 
+        override_thread_id(Some("main"));
+        override_no_color(Some(false));
         let result = msg.parse("[%t][%c][%[%s%]][%f:%l]: %m");
-        assert_eq!("[1970-01-01T00:00:00+00:00][ThreadId(2)][\x1b[31mlogtra\x1b[0m][lib.rs:12]: Hello world!", &result);
+        override_no_color(None);
+        override_thread_id(None);
+        assert_eq!(
+            "[1970-01-01T00:00:00+00:00][main][\x1b[31mlogtra\x1b[0m][lib.rs:12]: Hello world!",
+            &result
+        );
+    }
+
+    #[test]
+    fn millisecond_timestamp_works() {
+        let msg = LogMessage {
+            time: SystemTime::UNIX_EPOCH + Duration::from_millis(1_704_164_645_123),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+
+        assert_eq!("2024-01-02T03:04:05.123Z", msg.parse("%T"));
+        assert_eq!("2024-01-02T03:04:05.123+00:00", msg.parse("%t"));
+
+        let msg = LogMessage {
+            time: SystemTime::UNIX_EPOCH,
+            ..msg
+        };
+        assert_eq!("1970-01-01T00:00:00.000Z", msg.parse("%T"));
+    }
+
+    #[test]
+    fn local_timestamp_works() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_164_645);
+        let msg = LogMessage {
+            time,
+            ..message("Hello world!", LogSeverity::Info)
+        };
+
+        override_local_offset(FixedOffset::east_opt(2 * 3600));
+        let east = msg.parse("%L");
+        override_local_offset(FixedOffset::west_opt(8 * 3600));
+        let west = msg.parse("%L");
+        override_local_offset(FixedOffset::east_opt(0));
+        let utc = msg.parse("%L");
+        override_local_offset(None);
+
+        assert_eq!("2024-01-02T05:04:05+02:00", east);
+        assert_eq!("2024-01-01T19:04:05-08:00", west);
+        assert_eq!("2024-01-02T03:04:05+00:00", utc);
+        // %t stays on UTC
+        assert_eq!("2024-01-02T03:04:05+00:00", msg.parse("%t"));
+    }
+
+    #[test]
+    fn epoch_timestamp_works() {
+        let msg = LogMessage {
+            time: SystemTime::UNIX_EPOCH + Duration::from_millis(1_000_250),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+
+        assert_eq!("1000", msg.parse("%e"));
+        assert_eq!("1000250", msg.parse("%E"));
+        assert_eq!("001000", msg.parse("%06e"));
+    }
+
+    #[test]
+    fn custom_timestamp_works() {
+        let msg = LogMessage {
+            time: SystemTime::UNIX_EPOCH + Duration::from_millis(1_704_164_645_123),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+
+        assert_eq!("[2024-01-02T03:04:05.123+00:00]", msg.parse("[%t]"));
+        assert_eq!("[03:04:05]", msg.parse("[%t{%H:%M:%S}]"));
+        assert_eq!(
+            "2024-01-02 03:04:05 Hello world!",
+            msg.parse("%t{%Y-%m-%d %H:%M:%S} %m")
+        );
+        assert!(validate_template("[%t{%H:%M:%S}]: %m").is_ok());
+        assert!(matches!(
+            validate_template("[%t{%H:%Q}]: %m"),
+            Err(Error::TemplateParse(_))
+        ));
+        assert!(matches!(
+            validate_template("[%t{%H:%M]: %m"),
+            Err(Error::TemplateParse(_))
+        ));
+    }
+
+    #[test]
+    fn percent_escape_works() {
+        let msg = message("Hello world!", LogSeverity::Info);
+
+        let result = msg.parse("progress: 50%% done");
+        assert_eq!("progress: 50% done", result);
+        assert_eq!(1, result.matches('%').count());
+        assert_eq!("100%: logtra", msg.parse("100%%: %s"));
+        assert!(validate_template("progress: 50%% done").is_ok());
+    }
+
+    #[test]
+    fn parse_into_works() {
+        let msg = message("Hello world!", LogSeverity::Info);
+        let pattern = "[%i][%f:%l]: %m (%%)";
+
+        let mut buffer = "previous: ".to_string();
+        msg.parse_into(pattern, &mut buffer);
+        assert_eq!(format!("previous: {}", msg.parse(pattern)), buffer);
+
+        for _ in 0..3 {
+            buffer.clear();
+            msg.parse_into(pattern, &mut buffer);
+            assert_eq!(msg.parse(pattern), buffer);
+        }
+
+        let decl = SinkDeclaration {
+            template: "%m: ä".to_string(),
+            encoding: Encoding::Ascii,
+            ..Default::default()
+        };
+        let mut buffer = "ö ".to_string();
+        msg.render_into(&decl, &mut buffer);
+        assert_eq!("ö Hello world!: ?", buffer);
+    }
+
+    #[test]
+    fn compiled_template_works() {
+        let msg = LogMessage {
+            module: "logtra::msg",
+            file: "msg.rs",
+            line: 42,
+            tag: Some("http"),
+            fields: &[("status", "200")],
+            ..message("Hello World!", LogSeverity::Warn)
+        };
+        let pattern = "[%t][%[%i%]][%s][%f:%3l] %g: %m %k (100%%)\n";
+
+        let mut template = CompiledTemplate::new(pattern);
+        assert_eq!(pattern, template.pattern());
+        assert_eq!(msg.parse(pattern), msg.parse_compiled(&template));
+
+        let decl = SinkDeclaration {
+            template: pattern.to_string(),
+            ..Default::default()
+        };
+        let mut rendered = String::new();
+        msg.render_compiled_into(&template, &decl, &mut rendered);
+        assert_eq!(msg.render(&decl), rendered);
+
+        template.update("%m");
+        assert_eq!("%m", template.pattern());
+        assert_eq!("Hello World!", msg.parse_compiled(&template));
+    }
+
+    #[test]
+    fn thread_name_works() {
+        let msg = message("Hello world!", LogSeverity::Info);
+
+        let named = std::thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(move || msg.to_owned().parse("[%n]"))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!("[worker]", named);
+
+        let owned = msg.to_owned();
+        let (unnamed, id, numeric) = std::thread::spawn(move || {
+            (
+                owned.parse("%n"),
+                format!("{:?}", std::thread::current().id()),
+                owned.parse("%C"),
+            )
+        })
+        .join()
+        .unwrap();
+        assert_eq!(numeric, unnamed);
+        assert!(numeric.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(format!("ThreadId({})", numeric), id);
+    }
+
+    #[test]
+    fn process_id_works() {
+        let msg = message("Hello world!", LogSeverity::Info);
+
+        assert_eq!(std::process::id().to_string(), msg.parse("%p"));
+        assert_eq!(format!("[{:>10}]", std::process::id()), msg.parse("[%10p]"));
+    }
+
+    #[test]
+    fn thread_id_override_works() {
+        let msg = message("Hello world!", LogSeverity::Info);
+
+        override_thread_id(Some("worker-1"));
+        assert_eq!("[worker-1]", &msg.parse("[%c]"));
+        assert_eq!(msg.parse("[%c]"), msg.parse("[%c]"));
+
+        override_thread_id(None);
+        let thread_id = format!("[{:?}]", std::thread::current().id());
+        assert_eq!(thread_id, msg.parse("[%c]"));
+    }
+
+    #[test]
+    fn log_message_tag_parsing_works() {
+        let mut msg = LogMessage {
+            tag: Some("auth"),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+        assert_eq!("[auth]: Hello world!", &msg.parse("[%g]: %m"));
+
+        msg.tag = None;
+        assert_eq!("[]: Hello world!", &msg.parse("[%g]: %m"));
+    }
+
+    #[test]
+    fn logfmt_fields_work() {
+        let mut msg = message("Hello world!", LogSeverity::Info);
+        assert_eq!("[]", msg.parse("[%F]"));
+
+        msg.fields = &[("status", "200")];
+        assert_eq!("[status=200]", msg.parse("[%F]"));
+
+        msg.fields = &[
+            ("status", "200"),
+            ("path", "/a b"),
+            ("agent", "say \"hi\""),
+            ("query", "a=b"),
+            ("empty", ""),
+        ];
+        assert_eq!(
+            "status=200 path=\"/a b\" agent=\"say \\\"hi\\\"\" query=\"a=b\" empty=\"\"",
+            msg.parse("%F")
+        );
+        msg.fields = &[("status", "200"), ("path", "/x")];
+        assert_eq!("status=200, path=/x", msg.parse("%F{, }"));
+        assert!(validate_template("%m %F{ | }").is_ok());
+    }
+
+    #[test]
+    fn log_message_line_padding_works() {
+        let mut msg = LogMessage {
+            line: 7,
+            ..message("Hello world!", LogSeverity::Info)
+        };
+        assert_eq!("[00007][    7][7]", &msg.parse("[%05l][%5l][%l]"));
+
+        msg.line = 12345;
+        assert_eq!("[12345][12345][12345]", &msg.parse("[%05l][%5l][%l]"));
+
+        msg.line = u32::MAX;
+        assert_eq!("[4294967295][4294967295]", &msg.parse("[%05l][%5l]"));
+
+        assert_eq!("[lib.rs]", &msg.parse("[%05f]"));
+    }
+
+    #[test]
+    fn log_message_location_stripping_works() {
+        let msg = message("Hello world!", LogSeverity::Info);
+        let mut decl = SinkDeclaration {
+            template: "[%s][%f:%05l]: %m".to_string(),
+            ..Default::default()
+        };
+        assert_eq!("[logtra][lib.rs:00012]: Hello world!", &msg.render(&decl));
+
+        decl.strip_location = true;
+        assert_eq!("[logtra][:]: Hello world!", &msg.render(&decl));
+    }
+
+    #[test]
+    fn log_message_parsing_matches_naive_rendering() {
+        let msg = LogMessage {
+            module: "logtra::msg",
+            file: "msg.rs",
+            line: 42,
+            color: Color::Orange,
+            tag: Some("perf"),
+            ..message(
+                "a considerably longer message than the template it is rendered into",
+                LogSeverity::Warn,
+            )
+        };
+
+        override_thread_id(Some("main"));
+        let result = msg.parse("[%t][%c][%[%i%]][%g][%s][%f:%l]: %m\n");
+        override_thread_id(None);
+
+        let naive = format!(
+            "[{}][{}][{}{}{}][{}][{}][{}:{}]: {}\n",
+            "1970-01-01T00:00:00+00:00",
+            "main",
+            Color::Orange.ansi(),
+            "Warn ",
+            Color::Default.ansi(),
+            "perf",
+            "logtra::msg",
+            "msg.rs",
+            42,
+            "a considerably longer message than the template it is rendered into",
+        );
+        assert_eq!(naive, result);
+    }
+
+    #[test]
+    fn template_compilation_works() {
+        let template = Template::compile("[%05l] \\%%m%");
+        assert_eq!(
+            vec![
+                Segment::Literal("[".to_string()),
+                Segment::Pattern(
+                    'l',
+                    Padding {
+                        width: 5,
+                        zeroes: true
+                    }
+                ),
+                Segment::Literal("] %".to_string()),
+                Segment::Pattern('m', Padding::default()),
+            ],
+            template.segments
+        );
+    }
+
+    #[test]
+    fn no_color_works() {
+        use std::ffi::OsStr;
+
+        use super::{colors_disabled, no_color_requested};
+
+        assert!(no_color_requested(Some(OsStr::new("1"))));
+        assert!(!no_color_requested(Some(OsStr::new(""))));
+        assert!(!no_color_requested(None));
+
+        let msg = message("Hello world!", LogSeverity::Warn);
+
+        override_no_color(Some(true));
+        assert!(colors_disabled());
+        let rendered = msg.parse("[%[%i%]]: %m");
+        assert_eq!("", Color::Rgb(255, 128, 0).ansi());
+        override_no_color(Some(false));
+        let colored = msg.parse("[%[%i%]]: %m");
+        override_no_color(None);
+
+        assert!(!rendered.contains('\x1b'));
+        assert_eq!("[Warn ]: Hello world!", rendered);
+        assert_eq!("[\x1b[31mWarn \x1b[0m]: Hello world!", colored);
+    }
+
+    #[test]
+    fn severity_parsing_works() {
+        use crate::msg::LogSeverity;
+
+        for (names, severity) in [
+            (&["verbose", "verb"][..], LogSeverity::Verbose),
+            (&["trace"], LogSeverity::Trace),
+            (&["debug", "dbg"], LogSeverity::Debug),
+            (&["info", "information"], LogSeverity::Info),
+            (&["warn", "warning"], LogSeverity::Warn),
+            (&["error", "err"], LogSeverity::Error),
+            (&["fatal", "critical", "crit"], LogSeverity::Fatal),
+        ] {
+            for name in names {
+                assert_eq!(severity, name.parse().unwrap());
+                assert_eq!(severity, name.to_ascii_uppercase().parse().unwrap());
+            }
+            // the labels of %i parse back
+            assert_eq!(severity, severity.to_string().parse().unwrap());
+        }
+        assert_eq!(LogSeverity::Warn, "Warning".parse().unwrap());
+
+        let err = "loud".parse::<LogSeverity>().unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+        assert!(err.to_string().contains("unknown severity loud"));
+        assert!("".parse::<LogSeverity>().is_err());
+    }
+
+    #[test]
+    fn severity_labels_work() {
+        use crate::msg::LogSeverity;
+
+        let severities = [
+            LogSeverity::Verbose,
+            LogSeverity::Trace,
+            LogSeverity::Debug,
+            LogSeverity::Info,
+            LogSeverity::Warn,
+            LogSeverity::Error,
+            LogSeverity::Fatal,
+        ];
+        for severity in severities {
+            assert_eq!(severity.to_string(), severity.as_str());
+            assert_eq!(5, severity.as_str().len());
+        }
+
+        // as_str hands out the same static label every time instead of allocating a new one
+        let start = Instant::now();
+        let mut length = 0;
+        for i in 0..1_000_000 {
+            length += std::hint::black_box(severities[i % severities.len()].as_str()).len();
+        }
+        assert_eq!(5_000_000, length);
+        println!("as_str: 1000000 labels in {:?}", start.elapsed());
+        assert!(std::ptr::eq(
+            LogSeverity::Warn.as_str(),
+            LogSeverity::Warn.as_str()
+        ));
+    }
+
+    #[test]
+    fn color_parsing_works() {
+        assert_eq!(Color::Red, "red".parse().unwrap());
+        assert_eq!(Color::Red, "RED".parse().unwrap());
+        assert_eq!(Color::DarkRed, "dark_red".parse().unwrap());
+        assert_eq!(Color::DimGrey, "DimGray".parse().unwrap());
+        assert_eq!(Color::Rgb(255, 128, 0), "#ff8000".parse().unwrap());
+        assert_eq!(Color::Rgb(255, 128, 0), "#FF8000".parse().unwrap());
+
+        for malformed in ["purple", "#ff80", "#ff80001", "#gg8000", "ff8000", "#ff80é"] {
+            assert!(matches!(malformed.parse::<Color>(), Err(Error::Config(_))));
+        }
+    }
+
+    #[test]
+    fn styles_work() {
+        override_no_color(Some(false));
+        assert_eq!("\x1b[1;31m", Color::Red.styled(Style::BOLD));
+        assert_eq!("\x1b[2;31m", Color::Red.styled(Style::DIM));
+        assert_eq!("\x1b[3;31m", Color::Red.styled(Style::ITALIC));
+        assert_eq!("\x1b[4;31m", Color::Red.styled(Style::UNDERLINE));
+        assert_eq!(
+            "\x1b[1;4;38;2;255;128;0m",
+            Color::Rgb(255, 128, 0).styled(Style::BOLD | Style::UNDERLINE)
+        );
+        assert_eq!("\x1b[0;1m", Color::Default.styled(Style::BOLD));
+        assert_eq!(Color::Red.ansi(), Color::Red.styled(Style::NONE));
+
+        let msg = LogMessage {
+            color: Color::DarkRed,
+            style: Style::BOLD,
+            ..message("Hello world!", LogSeverity::Fatal)
+        };
+        let rendered = msg.parse("[%[%i%]]");
+        override_no_color(None);
+        assert_eq!("[\x1b[1;91mFatal\x1b[0m]", rendered);
+        assert!((Style::BOLD | Style::DIM).contains(Style::DIM));
+        assert!(!Style::BOLD.contains(Style::DIM));
+    }
+
+    #[test]
+    fn static_colors_work() {
+        use std::borrow::Cow;
+
+        override_no_color(Some(false));
+        for (color, previous) in [
+            (Color::Default, "\x1b[0m"),
+            (Color::DimGrey, "\x1b[2;90m"),
+            (Color::Grey, "\x1b[90m"),
+            (Color::Blue, "\x1b[34m"),
+            (Color::White, "\x1b[97m"),
+            (Color::Orange, "\x1b[33m"),
+            (Color::Red, "\x1b[31m"),
+            (Color::DarkRed, "\x1b[91m"),
+            (Color::Green, "\x1b[32m"),
+            (Color::Yellow, "\x1b[33m"),
+            (Color::Cyan, "\x1b[36m"),
+            (Color::Magenta, "\x1b[35m"),
+        ] {
+            assert!(matches!(color.ansi(), Cow::Borrowed(ansi) if ansi == previous));
+            assert!(matches!(color.styled(Style::NONE), Cow::Borrowed(_)));
+        }
+        assert!(matches!(Color::Rgb(1, 2, 3).ansi(), Cow::Owned(_)));
+        override_no_color(None);
+    }
+
+    #[test]
+    fn standard_colors_work() {
+        override_no_color(Some(false));
+        assert_eq!("\x1b[32m", Color::Green.ansi());
+        assert_eq!("\x1b[33m", Color::Yellow.ansi());
+        assert_eq!("\x1b[36m", Color::Cyan.ansi());
+        assert_eq!("\x1b[35m", Color::Magenta.ansi());
+        override_no_color(None);
+    }
+
+    #[test]
+    fn rgb_color_works() {
+        override_no_color(Some(false));
+        assert_eq!("\x1b[38;2;255;128;0m", Color::Rgb(255, 128, 0).ansi());
+        assert_eq!("\x1b[38;2;0;0;0m", Color::Rgb(0, 0, 0).ansi());
+        assert_eq!("\x1b[31m", Color::Red.ansi());
+        override_no_color(None);
+    }
+
+    #[test]
+    fn palette_works() {
+        let palette = Palette::from_config(
+            "# themed for the ops dashboard\n\nError = 1;38;5;196\n warn=33 \n",
+        )
+        .unwrap();
+        let mut msg = LogMessage {
+            module: "logtra::msg",
+            file: "msg.rs",
+            line: 42,
+            ..message("palette", LogSeverity::Error)
+        };
+
+        override_no_color(Some(false));
+        override_palette(Some(palette));
+        assert_eq!("\x1b[1;38;5;196mError\x1b[0m", msg.parse("%[%i%]"));
+        msg.severity = crate::msg::LogSeverity::Fatal;
+        msg.color = Color::DarkRed;
+        assert_eq!("\x1b[91mFatal\x1b[0m", msg.parse("%[%i%]"));
+        override_palette(None);
+
+        msg.severity = crate::msg::LogSeverity::Error;
+        msg.color = Color::Red;
+        assert_eq!("\x1b[31mError\x1b[0m", msg.parse("%[%i%]"));
+        override_no_color(None);
+
+        assert!(matches!(
+            Palette::from_config("Error = red"),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            Palette::from_config("Error = 1;;31"),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            Palette::from_config("Loud = 31"),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(Palette::from_config("31"), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn humanize_ago_works() {
+        let time: SystemTime = DateTime::<Utc>::default().into();
+        let ago = |secs: u64| humanize_ago_at(time, time + Duration::from_secs(secs));
+
+        assert_eq!("0s ago", ago(0));
+        assert_eq!("2s ago", ago(2));
+        assert_eq!("59s ago", ago(59));
+        assert_eq!("5m ago", ago(5 * 60 + 30));
+        assert_eq!("3h ago", ago(3 * 3600 + 59 * 60));
+        assert_eq!("2d ago", ago(2 * 86400));
+        assert_eq!(
+            "in 5m",
+            humanize_ago_at(time + Duration::from_secs(300), time)
+        );
+
+        let msg = LogMessage {
+            time: SystemTime::now() - Duration::from_secs(7200),
+            module: "logtra::msg",
+            file: "msg.rs",
+            line: 42,
+            color: Color::Default,
+            ..message("ago", LogSeverity::Info)
+        };
+        assert_eq!("[2h ago]: ago", msg.parse("[%A]: %m"));
+    }
+
+    #[test]
+    fn log_message_uptime_parsing_works() {
+        let mut msg = LogMessage {
+            uptime: Duration::from_millis(83_007),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+        assert_eq!("[83.007]", &msg.parse("[%U]"));
+
+        let first = crate::msg!(Info, Default, "first");
+        std::thread::sleep(Duration::from_millis(5));
+        let second = crate::msg!(Info, Default, "second");
+        assert!(second.uptime > first.uptime);
+
+        msg.uptime = first.uptime;
+        let first: f64 = msg.parse("%U").parse().unwrap();
+        msg.uptime = second.uptime;
+        let second: f64 = msg.parse("%U").parse().unwrap();
+        assert!(second > first);
+    }
+
+    #[test]
+    fn template_validation_works() {
+        assert!(validate_template("[%t][%c][%[%i%]][%s][%f:%05l]: %m\\%\n").is_ok());
+        assert!(matches!(
+            validate_template("[%t][%q]: %m"),
+            Err(Error::TemplateParse(_))
+        ));
+        assert!(matches!(
+            validate_template("[%t]: %m %"),
+            Err(Error::TemplateParse(_))
+        ));
+        assert!(validate_template("[%x{request_id}]: %m").is_ok());
+        assert!(matches!(
+            validate_template("[%x]: %m"),
+            Err(Error::TemplateParse(_))
+        ));
+        assert!(matches!(
+            validate_template("[%x{request_id]: %m"),
+            Err(Error::TemplateParse(_))
+        ));
+    }
+
+    #[test]
+    fn padding_validation_works() {
+        assert!(validate_template("[%05l][%5e][%013E][%8p]").is_ok());
+        assert!(validate_template(&format!("[%{}l]", MAX_PADDING)).is_ok());
+        for template in ["[%05m]", "[%5t]", "[%2%]", "[%3x{request_id}]"] {
+            assert!(
+                matches!(validate_template(template), Err(Error::TemplateParse(_))),
+                "{}",
+                template
+            );
+        }
+        for template in [
+            format!("[%{}l]", MAX_PADDING + 1),
+            "[%999999999l]".to_string(),
+            "[%99999999999999999999l]".to_string(),
+        ] {
+            assert!(
+                matches!(validate_template(&template), Err(Error::TemplateParse(_))),
+                "{}",
+                template
+            );
+        }
+
+        // unvalidated templates are capped instead of overflowing
+        let msg = message("Hello world!", LogSeverity::Info);
+        assert_eq!(
+            format!("{:0>width$}", 12, width = MAX_PADDING),
+            msg.parse("%099999999999999999999l")
+        );
+    }
+
+    #[test]
+    fn log_message_offset_parsing_works() {
+        let time = DateTime::<Utc>::default();
+        let msg = LogMessage {
+            time: time.into(),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+
+        override_local_offset(FixedOffset::east_opt(2 * 3600));
+        let east = msg.parse("%z");
+        override_local_offset(FixedOffset::west_opt(5 * 3600 + 30 * 60));
+        let west = msg.parse("%z");
+        override_local_offset(None);
+        assert_eq!("+02:00", east);
+        assert_eq!("-05:30", west);
+
+        let system = msg.parse("%z");
+        assert_eq!(6, system.len());
+        assert!(system.starts_with('+') || system.starts_with('-'));
+        assert!(matches!(
+            validate_template("%t %Z"),
+            Err(Error::TemplateParse(_))
+        ));
+    }
+
+    #[test]
+    fn log_message_ascii_encoding_works() {
+        let msg = message("Grüße aus Köln 👋", LogSeverity::Info);
+        let mut decl = SinkDeclaration {
+            template: "[%s]: %m".to_string(),
+            ..Default::default()
+        };
+        assert_eq!("[logtra]: Grüße aus Köln 👋", &msg.render(&decl));
+
+        decl.encoding = Encoding::Ascii;
+        assert_eq!("[logtra]: Gr??e aus K?ln ?", &msg.render(&decl));
+    }
+
+    #[test]
+    fn base64_works() {
+        // the test vectors of RFC 4648
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encoded, Base64(bytes.as_bytes()).to_string());
+        }
+        assert_eq!(
+            "3q2+7/8=",
+            Base64(&[0xde, 0xad, 0xbe, 0xef, 0xff]).to_string()
+        );
+
+        let payload = Base64(&[0xde, 0xad, 0xbe, 0xef]).to_string();
+        let mut msg = message("packet", LogSeverity::Info);
+        let fields = [("payload", payload.as_str())];
+        msg.fields = &fields;
+        assert!(msg
+            .render_json(&SinkDeclaration::default())
+            .ends_with(",\"fields\":{\"payload\":\"3q2+7w==\"}}"));
     }
 }
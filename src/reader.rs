@@ -0,0 +1,456 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Lines},
+    iter::Peekable,
+    path::Path,
+    str::Chars,
+    time::{Duration, SystemTime},
+};
+
+use chrono::DateTime;
+
+use crate::{
+    msg::{LogSeverity, OwnedLogMessage, Style},
+    Error, Result,
+};
+
+/// Streams the msgs of a log file back one line at a time, e.g. to analyse a file without loading it as a whole
+/// Reads what a [crate::sink::JsonSink] writes, a JSON object per line or, with array batches, a JSON array per line.
+/// Colors and styles are not written, so every msg gets the color of its severity and a zero uptime.
+pub struct LogReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+    pending: VecDeque<OwnedLogMessage>,
+}
+
+impl LogReader<BufReader<File>> {
+    /// Opens a file written by a [crate::sink::JsonSink]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> LogReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            line: 0,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LogReader<R> {
+    /// A line which is no valid msg yields an [crate::Error::Io] of kind `InvalidData`, reading continues with the next line
+    type Item = Result<OwnedLogMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(msg) = self.pending.pop_front() {
+                return Some(Ok(msg));
+            }
+
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_line(&line) {
+                Ok(msgs) => self.pending.extend(msgs),
+                Err(err) => {
+                    return Some(Err(Error::Io(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {}: {}", self.line, err),
+                    ))))
+                }
+            }
+        }
+    }
+}
+
+/// The JSON values a line can hold, numbers are kept as written
+#[derive(PartialEq, Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn parse_line(line: &str) -> std::result::Result<Vec<OwnedLogMessage>, String> {
+    let mut parser = Parser {
+        chars: line.chars().peekable(),
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if let Some(c) = parser.chars.next() {
+        return Err(format!("unexpected {:?} after the value", c));
+    }
+
+    match value {
+        Json::Array(values) => values.into_iter().map(to_msg).collect(),
+        value => Ok(vec![to_msg(value)?]),
+    }
+}
+
+fn to_msg(value: Json) -> std::result::Result<OwnedLogMessage, String> {
+    let Json::Object(entries) = value else {
+        return Err("expected a JSON object".to_string());
+    };
+
+    let mut time = None;
+    let mut severity = None;
+    let mut message = None;
+    let mut msg = OwnedLogMessage {
+        time: SystemTime::UNIX_EPOCH,
+        module: String::new(),
+        file: String::new(),
+        line: 0,
+        msg: String::new(),
+        severity: LogSeverity::Info,
+        color: LogSeverity::Info.color(),
+        style: Style::NONE,
+        tag: None,
+        fields: Vec::new(),
+        uptime: Duration::ZERO,
+    };
+    for (key, value) in entries {
+        match (key.as_str(), value) {
+            ("timestamp", Json::String(timestamp)) => {
+                let timestamp = DateTime::parse_from_rfc3339(&timestamp)
+                    .map_err(|err| format!("invalid timestamp {}: {}", timestamp, err))?;
+                time = Some(timestamp.into());
+            }
+            ("level", Json::String(level)) => {
+                severity = Some(
+                    level
+                        .parse::<LogSeverity>()
+                        .map_err(|err| err.to_string())?,
+                )
+            }
+            ("module", Json::String(module)) => msg.module = module,
+            ("file", Json::String(file)) => msg.file = file,
+            ("line", Json::Number(line)) => {
+                msg.line = line
+                    .parse()
+                    .map_err(|_| format!("invalid line number {}", line))?
+            }
+            ("message", Json::String(text)) => message = Some(text),
+            ("tag", Json::String(tag)) => msg.tag = Some(tag),
+            ("fields", Json::Object(fields)) => msg.fields = to_strings(fields)?,
+            (
+                "timestamp" | "level" | "module" | "file" | "line" | "message" | "tag" | "fields",
+                _,
+            ) => return Err(format!("invalid value of {}", key)),
+            // written by a newer version, or added by a shipper on the way
+            _ => {}
+        }
+    }
+
+    msg.time = time.ok_or("missing timestamp")?;
+    msg.severity = severity.ok_or("missing level")?;
+    msg.color = msg.severity.color();
+    msg.msg = message.ok_or("missing message")?;
+    Ok(msg)
+}
+
+fn to_strings(entries: Vec<(String, Json)>) -> std::result::Result<Vec<(String, String)>, String> {
+    entries
+        .into_iter()
+        .map(|(key, value)| match value {
+            Json::String(value) => Ok((key, value)),
+            _ => Err(format!("invalid value of {}, expected a string", key)),
+        })
+        .collect()
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> std::result::Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected {:?}, found {:?}", expected, c)),
+            None => Err(format!(
+                "expected {:?}, found the end of the line",
+                expected
+            )),
+        }
+    }
+
+    fn value(&mut self) -> std::result::Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('n') => self.literal("null", Json::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => Ok(self.number()),
+            Some(c) => Err(format!("unexpected {:?}", c)),
+            None => Err("unexpected end of the line".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> std::result::Result<Json, String> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(entries)),
+                _ => return Err("expected ',' or '}' in object".to_string()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> std::result::Result<Json, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(values)),
+                _ => return Err("expected ',' or ']' in array".to_string()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> std::result::Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('/') => string.push('/'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => string.push(self.unicode_escape()?),
+                    _ => return Err("invalid escape sequence".to_string()),
+                },
+                Some(c) => string.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    /// Decodes the digits following a `\u`, including a following low surrogate
+    fn unicode_escape(&mut self) -> std::result::Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+                return Err("unpaired surrogate".to_string());
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err("unpaired surrogate".to_string());
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| "invalid unicode escape".to_string())
+    }
+
+    fn hex4(&mut self) -> std::result::Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or("invalid unicode escape")?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Json {
+        let mut number = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            number.push(c);
+        }
+        Json::Number(number)
+    }
+
+    fn literal(&mut self, literal: &str, value: Json) -> std::result::Result<Json, String> {
+        for expected in literal.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(format!("expected {}", literal));
+            }
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::{remove_file, File},
+        io::{Cursor, Write},
+        time::Duration,
+    };
+
+    use chrono::{DateTime, Utc};
+
+    use crate::{
+        msg::{Color, LogMessage, LogSeverity, Style},
+        reader::LogReader,
+        sink::{JsonSink, Sink, SinkDeclaration},
+        Error,
+    };
+
+    fn message(msg: &str, severity: LogSeverity) -> LogMessage<'_> {
+        LogMessage {
+            time: DateTime::<Utc>::default().into(),
+            module: "logtra::reader",
+            file: "reader.rs",
+            line: 42,
+            msg,
+            severity,
+            color: Color::Default,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn json_lines_are_read_back() {
+        let path = std::env::temp_dir().join(format!("logtra-{}-reader.log", std::process::id()));
+        let decl = SinkDeclaration {
+            name: "json".to_string(),
+            severity: LogSeverity::Verbose,
+            ..Default::default()
+        };
+        let mut sink = JsonSink::new(decl, File::create(&path).unwrap());
+        let mut first = message("first \"quoted\"\nline", LogSeverity::Verbose);
+        first.tag = Some("audit");
+        first.fields = &[("status", "200"), ("user", "ünïcode")];
+        sink.log_filtered(&first);
+        let mut second = message("second", LogSeverity::Fatal);
+        second.fields = &[("status", "500"), ("request_id", "7")];
+        sink.log_filtered(&second);
+        assert!(sink.flush().is_ok());
+        drop(sink);
+
+        let msgs: Vec<_> = LogReader::open(&path)
+            .unwrap()
+            .collect::<crate::Result<_>>()
+            .unwrap();
+        assert_eq!(2, msgs.len());
+        let mut expected = first.to_owned();
+        expected.color = LogSeverity::Verbose.color();
+        assert_eq!(expected, msgs[0]);
+        assert_eq!("second", msgs[1].msg);
+        assert_eq!(LogSeverity::Fatal, msgs[1].severity);
+        assert_eq!(
+            vec![
+                ("status".to_string(), "500".to_string()),
+                ("request_id".to_string(), "7".to_string())
+            ],
+            msgs[1].fields
+        );
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[test]
+    fn json_arrays_are_read_back() {
+        let decl = SinkDeclaration {
+            name: "json".to_string(),
+            strip_location: true,
+            ..Default::default()
+        };
+        let mut sink = JsonSink::new(decl, Vec::new()).with_array_batches(2);
+        for text in ["first", "second", "third"] {
+            sink.log_filtered(&message(text, LogSeverity::Info));
+        }
+        assert!(sink.flush().is_ok());
+
+        let written = sink.writer().clone();
+        let msgs: Vec<String> = LogReader::new(Cursor::new(written))
+            .map(|msg| msg.unwrap().msg)
+            .collect();
+        assert_eq!(vec!["first", "second", "third"], msgs);
+    }
+
+    #[test]
+    fn invalid_lines_are_reported() {
+        let mut log = Vec::new();
+        writeln!(
+            log,
+            "{{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"Info\",\"message\":\"valid\"}}"
+        )
+        .unwrap();
+        writeln!(log).unwrap();
+        writeln!(log, "plain text").unwrap();
+        writeln!(
+            log,
+            "{{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"loud\",\"message\":\"\"}}"
+        )
+        .unwrap();
+        writeln!(log, "{{\"level\":\"Info\",\"message\":\"no time\"}}").unwrap();
+        writeln!(
+            log,
+            "{{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"Warn\",\"message\":\"\\u00e9\\ud83d\\ude00\",\"host\":null}}"
+        )
+        .unwrap();
+
+        let results: Vec<_> = LogReader::new(Cursor::new(log)).collect();
+        assert_eq!(5, results.len());
+        assert_eq!("valid", results[0].as_ref().unwrap().msg);
+        let errors: Vec<String> = results[1..4]
+            .iter()
+            .map(|result| match result {
+                Err(Error::Io(err)) => {
+                    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+                    err.to_string()
+                }
+                _ => panic!("expected an invalid data error"),
+            })
+            .collect();
+        assert!(errors[0].starts_with("line 3: "));
+        assert!(errors[1].starts_with("line 4: invalid configuration: unknown severity loud"));
+        assert_eq!("line 5: missing timestamp", errors[2]);
+        assert_eq!("é😀", results[4].as_ref().unwrap().msg);
+    }
+}
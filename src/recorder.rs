@@ -0,0 +1,231 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard, TryLockError},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::msg::LogMessage;
+
+const DEFAULT_CAPACITY: usize = 128;
+
+/// Keeps the most recent msgs, regardless of any sink filter
+struct FlightRecorder {
+    capacity: usize,
+    messages: VecDeque<(SystemTime, String)>,
+}
+
+impl FlightRecorder {
+    const fn new(capacity: usize) -> Self {
+        FlightRecorder {
+            capacity,
+            messages: VecDeque::new(),
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.messages.len() > capacity {
+            self.messages.pop_front();
+        }
+    }
+
+    fn push(&mut self, time: SystemTime, line: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.messages.len() >= self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((time, line));
+    }
+
+    fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.messages
+            .iter()
+            .map(|(time, line)| format!("[{}]{}", DateTime::<Utc>::from(*time).to_rfc3339(), line))
+    }
+
+    /// Writes the recorded msgs, oldest first, followed by the reason of the dump
+    fn dump(&self, out: &mut impl Write, reason: &str) -> std::io::Result<()> {
+        for line in self.lines() {
+            writeln!(out, "{}", line)?;
+        }
+        writeln!(out, "{}", reason)
+    }
+}
+
+/// Renders like "[%i][%s][%f:%l]: %m", the timestamp is only formatted when dumping
+fn render(msg: &LogMessage) -> String {
+    use std::fmt::Write as _;
+
+    let mut line = String::with_capacity(msg.module.len() + msg.file.len() + msg.msg.len() + 24);
+    line.push('[');
+    line.push_str(msg.severity.as_str());
+    line.push_str("][");
+    line.push_str(msg.module);
+    line.push_str("][");
+    line.push_str(msg.file);
+    let _ = write!(line, ":{}]: ", msg.line);
+    line.push_str(msg.msg);
+    line
+}
+
+static RECORDER: Mutex<FlightRecorder> = Mutex::new(FlightRecorder::new(DEFAULT_CAPACITY));
+
+fn recorder() -> MutexGuard<'static, FlightRecorder> {
+    lock(&RECORDER)
+}
+
+fn lock(recorder: &Mutex<FlightRecorder>) -> MutexGuard<'_, FlightRecorder> {
+    recorder.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Sets how many of the most recent msgs are kept for a crash dump, 0 disables the recorder
+pub fn set_recorder_capacity(capacity: usize) {
+    recorder().set_capacity(capacity);
+}
+
+/// Returns the most recent msgs, oldest first
+pub fn recorded() -> Vec<String> {
+    recorder().lines().collect()
+}
+
+/// Writes the most recent msgs to the given path whenever a thread panics
+/// The previously installed panic hook is still called afterwards.
+pub fn install_crash_dump(path: impl Into<PathBuf>) {
+    install_crash_dump_of(&RECORDER, path.into());
+}
+
+fn install_crash_dump_of(recorder: &'static Mutex<FlightRecorder>, path: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Err(err) = dump(recorder, &path, &info.to_string()) {
+            eprintln!("logtra: failed to write crash dump to {:?}: {}", path, err);
+        }
+        previous(info);
+    }));
+}
+
+fn dump(recorder: &Mutex<FlightRecorder>, path: &PathBuf, reason: &str) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    // the panic may have happened while the recorder was locked on this thread
+    match recorder.try_lock() {
+        Ok(recorder) => recorder.dump(&mut file, reason),
+        Err(TryLockError::Poisoned(err)) => err.into_inner().dump(&mut file, reason),
+        Err(TryLockError::WouldBlock) => FlightRecorder::new(0).dump(&mut file, reason),
+    }
+}
+
+#[doc(hidden)]
+/// Used by publish! to keep every msg in the flight recorder
+/// The msg is rendered before the recorder is locked, so logging threads only wait for each other to append a line.
+pub fn record(msg: &LogMessage) {
+    record_into(&RECORDER, msg);
+}
+
+fn record_into(recorder: &Mutex<FlightRecorder>, msg: &LogMessage) {
+    let line = render(msg);
+    lock(recorder).push(msg.time, line);
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::Mutex,
+        time::{Duration, SystemTime},
+    };
+
+    use chrono::{DateTime, Utc};
+
+    use crate::{
+        msg::{Color, LogMessage, LogSeverity, Style},
+        recorder::{install_crash_dump_of, record_into, FlightRecorder},
+    };
+
+    fn message(msg: &str, severity: LogSeverity) -> LogMessage<'_> {
+        LogMessage {
+            time: DateTime::<Utc>::default().into(),
+            module: "logtra::recorder",
+            file: "recorder.rs",
+            line: 42,
+            msg,
+            severity,
+            color: Color::Default,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn flight_recorder_works() {
+        let recorder = Mutex::new(FlightRecorder::new(2));
+
+        record_into(&recorder, &message("evicted", LogSeverity::Info));
+        record_into(
+            &recorder,
+            &message("filtered by every sink", LogSeverity::Trace),
+        );
+        record_into(&recorder, &message("before the panic", LogSeverity::Warn));
+        let mut recorder = recorder.into_inner().unwrap();
+
+        let mut dump = Vec::new();
+        recorder.dump(&mut dump, "panicked").unwrap();
+        assert_eq!(
+            "[1970-01-01T00:00:00+00:00][Trace][logtra::recorder][recorder.rs:42]: filtered by every sink\n\
+             [1970-01-01T00:00:00+00:00][Warn ][logtra::recorder][recorder.rs:42]: before the panic\n\
+             panicked\n",
+            String::from_utf8(dump).unwrap()
+        );
+
+        recorder.set_capacity(1);
+        assert_eq!(
+            vec!["[1970-01-01T00:00:00+00:00][Warn ][logtra::recorder][recorder.rs:42]: before the panic"],
+            recorder.lines().collect::<Vec<_>>()
+        );
+        recorder.set_capacity(0);
+        recorder.push(SystemTime::UNIX_EPOCH, "disabled".to_string());
+        assert_eq!(0, recorder.lines().count());
+    }
+
+    #[test]
+    fn crash_dump_works() {
+        static RECORDER: Mutex<FlightRecorder> = Mutex::new(FlightRecorder::new(8));
+
+        // recorded by concurrent threads, none of them may be lost
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                scope.spawn(move || {
+                    let line = format!("thread {}", thread);
+                    record_into(&RECORDER, &message(&line, LogSeverity::Info));
+                });
+            }
+        });
+        record_into(&RECORDER, &message("before the panic", LogSeverity::Error));
+
+        let path = std::env::temp_dir().join(format!("logtra-{}-crash.dump", std::process::id()));
+        install_crash_dump_of(&RECORDER, path.clone());
+        let panicked = std::thread::spawn(|| panic!("crash dump: worker failed")).join();
+        assert!(panicked.is_err());
+        // restores the default hook, later panics of other tests should not write the dump again
+        let _ = std::panic::take_hook();
+
+        let dump = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        for thread in 0..4 {
+            assert!(dump.contains(&format!(
+                "[1970-01-01T00:00:00+00:00][Info ][logtra::recorder][recorder.rs:42]: thread {}\n",
+                thread
+            )));
+        }
+        assert!(dump.contains(
+            "[1970-01-01T00:00:00+00:00][Error][logtra::recorder][recorder.rs:42]: before the panic\npanicked at"
+        ));
+    }
+}
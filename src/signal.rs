@@ -0,0 +1,104 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    thread,
+    time::Duration,
+};
+
+const SIGHUP: i32 = 1;
+/// How often the helper thread checks whether a SIGHUP arrived
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+extern "C" {
+    fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+}
+
+static REOPEN: AtomicBool = AtomicBool::new(false);
+static INSTALL: Once = Once::new();
+
+/// Only sets a flag, as nothing else is safe to do inside a signal handler
+extern "C" fn on_sighup(_signum: i32) {
+    REOPEN.store(true, Ordering::Relaxed);
+}
+
+/// Reopens the files of every registered [crate::sink::Sink] whenever the process receives SIGHUP, e.g. from logrotate
+/// The reopen happens on a helper thread shortly after the signal. Installing it more than once has no effect.
+pub fn install_sighup_reopen() {
+    INSTALL.call_once(|| {
+        unsafe {
+            signal(SIGHUP, on_sighup);
+        }
+        thread::Builder::new()
+            .name("logtra-sighup".to_string())
+            .spawn(|| loop {
+                thread::sleep(POLL_INTERVAL);
+                if REOPEN.swap(false, Ordering::Relaxed) {
+                    if let Err(err) = crate::reopen_all() {
+                        eprintln!("logtra: failed to reopen after SIGHUP: {}", err);
+                    }
+                }
+            })
+            .expect("failed to spawn the SIGHUP helper thread");
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::{read_to_string, remove_dir_all, rename},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        msg, publish,
+        signal::{install_sighup_reopen, SIGHUP},
+        sink::{ShardedFileSink, SinkDeclaration},
+        tagged,
+    };
+
+    extern "C" {
+        fn raise(signum: i32) -> i32;
+    }
+
+    #[test]
+    fn sighup_reopen_works() {
+        let dir = std::env::temp_dir().join(format!("logtra-{}-sighup", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        let path = dir.join("logtra.log");
+        let rotated = dir.join("logtra.log.1");
+        let sink = ShardedFileSink::new(
+            SinkDeclaration {
+                name: dir.to_str().unwrap().to_string(),
+                template: "%m\n".to_string(),
+                tag: Some("sighup".to_string()),
+                ..Default::default()
+            },
+            1,
+        )
+        .unwrap();
+        crate::sink!(sink);
+        install_sighup_reopen();
+
+        tagged!("sighup", Info, "sighup: before the rotation");
+        rename(&path, &rotated).unwrap();
+        assert_eq!(0, unsafe { raise(SIGHUP) });
+
+        // msgs still reach the rotated file until the helper thread reopened the shard
+        let start = Instant::now();
+        while !path.exists() && start.elapsed() < Duration::from_secs(5) {
+            tagged!("sighup", Info, "sighup: after the rotation");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let rotated = read_to_string(&rotated).unwrap();
+        assert!(rotated.starts_with("sighup: before the rotation\n"));
+        assert_eq!(
+            "sighup: after the rotation\n",
+            read_to_string(&path).unwrap()
+        );
+        assert!(remove_dir_all(&dir).is_ok());
+    }
+}
@@ -1,11 +1,24 @@
 use std::{
-    fs::File,
-    io::{self, Write},
+    cell::Cell,
+    collections::{hash_map::RandomState, HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    io::Write,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
 };
 
 use contra::{Deserialize, Serialize};
 
-use crate::msg::{LogMessage, LogSeverity};
+use crate::{
+    msg::{
+        validate_template, Color, CompiledTemplate, LogMessage, LogSeverity, OwnedLogMessage, Style,
+    },
+    Error, Result,
+};
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct SinkDeclaration {
@@ -13,108 +26,736 @@ pub struct SinkDeclaration {
     pub(crate) severity: LogSeverity,
     pub(crate) module: String,
     pub(crate) template: String,
+    pub(crate) tag: Option<String>,
+    /// Renders %f and %l empty, e.g. to not leak source paths in release logs
+    pub(crate) strip_location: bool,
+    /// Renders %[ and %] empty, e.g. to keep escape sequences out of log files
+    pub(crate) strip_colors: bool,
+    /// The color %[ starts per severity, e.g. `Color::Rgb(255, 128, 0)`, instead of the color of the msg
+    pub(crate) colors: HashMap<LogSeverity, Color>,
+    /// The percentage of msgs kept per severity, severities without an entry are always kept
+    pub(crate) sampling: HashMap<LogSeverity, u8>,
+    /// Makes sampling deterministic, identically seeded sinks keep the same msgs
+    pub(crate) sampling_seed: Option<u64>,
+    /// Buffering sinks flush right after logging a msg of at least this severity
+    pub(crate) flush_severity: Option<LogSeverity>,
+    /// [crate::flush_all] and [crate::reopen_all] reach sinks in ascending priority, e.g. a sink fed by others goes after them
+    pub(crate) flush_priority: i32,
+    /// Disabled sinks drop every msg, but keep their configuration and state
+    pub(crate) enabled: bool,
+    /// Applied to the rendered msg, e.g. for legacy targets which only accept ASCII
+    pub(crate) encoding: Encoding,
+}
+
+/// The character set a sink writes its rendered msgs in
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum Encoding {
+    /// Writes the rendered msg as UTF-8
+    #[default]
+    Passthrough,
+    /// Replaces every non-ASCII character with a '?'
+    Ascii,
+}
+
+impl Encoding {
+    pub fn encode(&self, rendered: String) -> String {
+        match self {
+            Encoding::Passthrough => rendered,
+            Encoding::Ascii if rendered.is_ascii() => rendered,
+            Encoding::Ascii => rendered
+                .chars()
+                .map(|c| if c.is_ascii() { c } else { '?' })
+                .collect(),
+        }
+    }
+}
+
+impl SinkDeclaration {
+    /// Returns whether a buffering sink should flush right after logging a msg of the given severity
+    pub fn flushes_on(&self, severity: LogSeverity) -> bool {
+        self.flush_severity
+            .is_some_and(|flush_severity| severity >= flush_severity)
+    }
+
+    /// Checks that the template only contains known % patterns
+    pub fn validate(&self) -> Result<()> {
+        validate_template(&self.template)
+    }
+
+    /// Returns the percentage of msgs of the given severity which should be kept
+    pub fn sample_rate(&self, severity: LogSeverity) -> u8 {
+        self.sampling.get(&severity).copied().unwrap_or(100)
+    }
+}
+
+thread_local! {
+    static SAMPLING_STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// Randomly decides whether a msg is kept, keeping `percent` out of every hundred on average
+/// With a seed the decision is derived from the seed and the msg alone, so it is the same for every identically seeded sink
+/// and every run. Msgs which are equal down to their timestamp are therefore all kept or all dropped.
+fn sample(percent: u8, seed: Option<u64>, msg: &LogMessage) -> bool {
+    let random = match seed {
+        Some(seed) => {
+            // unlike RandomState, a new DefaultHasher always starts with the same keys
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            hasher.write_u64(seed);
+            hasher.write_u128(
+                msg.time
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos(),
+            );
+            hasher.write(msg.module.as_bytes());
+            hasher.write(msg.file.as_bytes());
+            hasher.write_u32(msg.line);
+            hasher.write(msg.msg.as_bytes());
+            hasher.finish()
+        }
+        None => SAMPLING_STATE.with(|state| {
+            // xorshift64*
+            let mut x = state.get();
+            x ^= x >> 12;
+            x ^= x << 25;
+            x ^= x >> 27;
+            state.set(x);
+            x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }),
+    };
+    random % 100 < percent as u64
+}
+
+impl Default for SinkDeclaration {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            severity: LogSeverity::Info,
+            module: String::new(),
+            template: "[%t][%c][%[%i%]][%s][%f:%l]: %m\n".to_string(),
+            tag: None,
+            strip_location: false,
+            strip_colors: false,
+            colors: HashMap::new(),
+            sampling: HashMap::new(),
+            sampling_seed: None,
+            flush_severity: None,
+            flush_priority: 0,
+            enabled: true,
+            encoding: Encoding::Passthrough,
+        }
+    }
+}
+
+/// The built-in sinks which can be created from a [crate::sink::SinkDeclaration] alone
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SinkKind {
+    Console,
+    Stderr,
+    File,
+    Void,
+    Debug,
+}
+
+/// The serializable configuration of a registered [crate::sink::Sink], see [crate::current_config] and [crate::reconcile]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct SinkConfigEntry {
+    pub kind: SinkKind,
+    pub decl: SinkDeclaration,
+}
+
+impl SinkConfigEntry {
+    /// Creates the sink described by this entry
+    pub fn build(&self) -> Result<Box<dyn Sink>> {
+        Ok(match self.kind {
+            SinkKind::Console => Box::new(ConsoleSink::new(self.decl.clone())),
+            SinkKind::Stderr => Box::new(StderrSink::new(self.decl.clone())),
+            SinkKind::File => Box::new(FileSink::new(self.decl.clone())?),
+            SinkKind::Void => Box::new(VoidSink::new(self.decl.clone())),
+            SinkKind::Debug => Box::new(DebugSink::new(self.decl.clone())),
+        })
+    }
+}
+
+/// Describes the optional features a [crate::sink::Sink] supports
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct SinkCapabilities {
+    /// Renders or forwards structured fields attached to a msg
+    pub structured_fields: bool,
+    /// Renders the color patterns of its template
+    pub color: bool,
+    /// Buffers msgs and writes them out on flush
+    pub flushable: bool,
+    /// Processes msgs off the logging thread
+    pub asynchronous: bool,
 }
 
 /// The Logger trait of Logtra
 /// Every instance of a [crate::sink::Sink] must be registered via [crate::sink!] in order to receive logs to process
-pub trait Sink: Sync + 'static {
+pub trait Sink: Send + 'static {
     fn log(&mut self, msg: &LogMessage);
 
-    /// Pre-filters received msg based on [crate::sink::Sink::severity] and [crate::sink::Sink::module]
+    /// Drops every msg while the sink is not [crate::sink::Sink::enabled]
+    /// Pre-filters received msg based on [crate::sink::Sink::severity], [crate::sink::Sink::module] and [crate::sink::Sink::tag]
+    /// and randomly drops msgs according to [crate::sink::Sink::sample_rate]
     fn log_filtered(&mut self, msg: &LogMessage) {
+        if !self.enabled() {
+            return;
+        }
         if self.severity() > msg.severity {
             return;
         }
         if !msg.module.contains(self.module()) {
             return;
         }
+        if self.tag().is_some() && msg.tag != self.tag() {
+            return;
+        }
+        let sample_rate = self.sample_rate(msg.severity);
+        if sample_rate < 100 && !sample(sample_rate, self.sampling_seed(), msg) {
+            return;
+        }
 
         self.log(msg);
     }
 
+    /// Returns the name of the sink, as given by its [crate::sink::SinkDeclaration]
+    fn name(&self) -> &str {
+        ""
+    }
+    /// Returns whether the sink currently processes msgs
+    fn enabled(&self) -> bool {
+        true
+    }
+    /// Pauses or resumes the sink without losing its configuration or state
+    fn set_enabled(&mut self, _enabled: bool) {}
     /// Returns the severity which must be matched or exceeded by the receiving msg to be logged
     fn severity(&self) -> LogSeverity;
+    /// Sets the severity which must be matched or exceeded by the receiving msg to be logged
+    /// Changes the declaration of the sink if it has one, otherwise the severity is fixed.
+    fn set_severity(&mut self, severity: LogSeverity) {
+        if let Some(decl) = self.declaration_mut() {
+            decl.severity = severity;
+        }
+    }
     /// Returns the module in which the receiving msg must be to be logged
     fn module(&self) -> &str;
+    /// Returns the tag which the receiving msg must carry to be logged, if any
+    fn tag(&self) -> Option<&str> {
+        None
+    }
+    /// Returns the percentage of msgs of the given severity which should be kept
+    fn sample_rate(&self, _severity: LogSeverity) -> u8 {
+        100
+    }
+    /// Returns the seed which makes sampling deterministic, if any
+    fn sampling_seed(&self) -> Option<u64> {
+        None
+    }
+    /// Returns the optional features supported by this sink
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities::default()
+    }
+    /// Returns the configuration this sink can be recreated from, if it can be
+    fn config(&self) -> Option<SinkConfigEntry> {
+        None
+    }
+    /// Closes and reopens the files of this sink by name, e.g. after they were rotated externally
+    fn reopen(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Writes out every msg the sink still holds back, e.g. before a controlled shutdown
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Returns the order in which [crate::flush_all] and [crate::reopen_all] reach this sink, lower first
+    /// Sinks of the same priority are reached in the order they were registered.
+    fn flush_priority(&self) -> i32 {
+        0
+    }
+    /// Returns the declaration this sink renders with, if it has one
+    fn declaration_mut(&mut self) -> Option<&mut SinkDeclaration> {
+        None
+    }
+}
+
+/// Implements the [crate::sink::Sink] accessors for sinks which keep their [crate::sink::SinkDeclaration] in `self.decl`
+macro_rules! decl_accessors {
+    () => {
+        fn name(&self) -> &str {
+            &self.decl.name
+        }
+
+        fn enabled(&self) -> bool {
+            self.decl.enabled
+        }
+
+        fn set_enabled(&mut self, enabled: bool) {
+            self.decl.enabled = enabled;
+        }
+
+        fn severity(&self) -> LogSeverity {
+            self.decl.severity
+        }
+
+        fn set_severity(&mut self, severity: LogSeverity) {
+            self.decl.severity = severity;
+        }
+
+        fn module(&self) -> &str {
+            &self.decl.module
+        }
+
+        fn tag(&self) -> Option<&str> {
+            self.decl.tag.as_deref()
+        }
+
+        fn sample_rate(&self, severity: LogSeverity) -> u8 {
+            self.decl.sample_rate(severity)
+        }
+
+        fn sampling_seed(&self) -> Option<u64> {
+            self.decl.sampling_seed
+        }
+
+        fn flush_priority(&self) -> i32 {
+            self.decl.flush_priority
+        }
+
+        fn declaration_mut(&mut self) -> Option<&mut SinkDeclaration> {
+            Some(&mut self.decl)
+        }
+    };
 }
 
+/// Prints every rendered msg to stdout
+/// Colors are stripped while stdout is not a terminal, e.g. when it is piped or redirected to a file.
 pub struct ConsoleSink {
     decl: SinkDeclaration,
+    template: CompiledTemplate,
+    colors: bool,
+    buffer: String,
 }
 
 impl ConsoleSink {
     pub fn new(decl: SinkDeclaration) -> Self {
-        ConsoleSink { decl }
+        use std::io::IsTerminal;
+
+        ConsoleSink {
+            template: CompiledTemplate::new(&decl.template),
+            decl,
+            colors: std::io::stdout().is_terminal(),
+            buffer: String::new(),
+        }
+    }
+
+    /// Forces colors on or off, instead of detecting whether stdout is a terminal
+    pub fn with_colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Renders the msg into the reused buffer
+    fn render(&mut self, msg: &LogMessage) -> &str {
+        // stripping only while rendering keeps the declaration as configured, e.g. for reconcile
+        let strip_colors = self.decl.strip_colors;
+        self.decl.strip_colors |= !self.colors;
+        self.buffer.clear();
+        self.template.update(&self.decl.template);
+        msg.render_compiled_into(&self.template, &self.decl, &mut self.buffer);
+        self.decl.strip_colors = strip_colors;
+        &self.buffer
     }
 }
 
 impl Sink for ConsoleSink {
     fn log(&mut self, msg: &LogMessage) {
-        print!("{}", msg.parse(&self.decl.template));
+        let rendered = self.render(msg);
+        print!("{}", rendered);
     }
 
-    fn severity(&self) -> LogSeverity {
-        self.decl.severity
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            color: true,
+            ..Default::default()
+        }
     }
 
-    fn module(&self) -> &str {
-        &self.decl.module
+    fn config(&self) -> Option<SinkConfigEntry> {
+        Some(SinkConfigEntry {
+            kind: SinkKind::Console,
+            decl: self.decl.clone(),
+        })
+    }
+
+    decl_accessors!();
+}
+
+/// Prints like the [crate::sink::ConsoleSink], but to stderr, keeping stdout clean for the output of the program
+pub struct StderrSink {
+    decl: SinkDeclaration,
+}
+
+impl StderrSink {
+    pub fn new(decl: SinkDeclaration) -> Self {
+        StderrSink { decl }
+    }
+}
+
+impl Sink for StderrSink {
+    fn log(&mut self, msg: &LogMessage) {
+        eprint!("{}", msg.render(&self.decl));
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            color: true,
+            ..Default::default()
+        }
+    }
+
+    fn config(&self) -> Option<SinkConfigEntry> {
+        Some(SinkConfigEntry {
+            kind: SinkKind::Stderr,
+            decl: self.decl.clone(),
+        })
+    }
+
+    decl_accessors!();
+}
+
+/// Prints msgs below the threshold to stdout and all others to stderr, so warnings and errors can be redirected separately
+pub struct SplitConsoleSink {
+    decl: SinkDeclaration,
+    threshold: LogSeverity,
+}
+
+impl SplitConsoleSink {
+    pub fn new(decl: SinkDeclaration) -> Self {
+        SplitConsoleSink {
+            decl,
+            threshold: LogSeverity::Warn,
+        }
+    }
+
+    /// Sets the lowest severity printed to stderr, by default [crate::msg::LogSeverity::Warn]
+    pub fn with_threshold(mut self, threshold: LogSeverity) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    fn to_stderr(&self, severity: LogSeverity) -> bool {
+        severity >= self.threshold
+    }
+}
+
+impl Sink for SplitConsoleSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let rendered = msg.render(&self.decl);
+        match self.to_stderr(msg.severity) {
+            true => eprint!("{}", rendered),
+            false => print!("{}", rendered),
+        }
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            color: true,
+            ..Default::default()
+        }
+    }
+
+    decl_accessors!();
+}
+
+/// The lines a [crate::sink::BufferedConsoleSink] has not written yet
+#[derive(Default)]
+struct ConsoleBuffer {
+    lines: VecDeque<String>,
+    /// Dropped since the last notice
+    dropped: u64,
+    closed: bool,
+}
+
+/// Prints like the [crate::sink::ConsoleSink], but from a thread of its own, so a slow or paused terminal never blocks logging
+/// Keeps at most `capacity` lines waiting. When full, the oldest waiting line is dropped,
+/// and a `logtra: dropped N console lines` notice is printed before the next written line.
+/// Dropping the sink waits until every waiting line is written.
+pub struct BufferedConsoleSink {
+    decl: SinkDeclaration,
+    capacity: usize,
+    buffer: Arc<(Mutex<ConsoleBuffer>, Condvar)>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl BufferedConsoleSink {
+    /// Creates a sink printing to stdout
+    pub fn new(decl: SinkDeclaration, capacity: usize) -> Result<Self> {
+        Self::with_writer(decl, capacity, std::io::stdout())
+    }
+
+    /// Creates a sink printing to the given writer instead of stdout
+    pub fn with_writer(
+        decl: SinkDeclaration,
+        capacity: usize,
+        mut writer: impl Write + Send + 'static,
+    ) -> Result<Self> {
+        if capacity == 0 {
+            return Err(Error::Config(
+                "a buffered console sink must be allowed to hold at least one line".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        let buffer = Arc::new((Mutex::new(ConsoleBuffer::default()), Condvar::new()));
+        let shared = buffer.clone();
+        let writer = std::thread::Builder::new()
+            .name("logtra-console".to_string())
+            .spawn(move || loop {
+                let (lock, available) = &*shared;
+                let mut buffer = lock.lock().unwrap_or_else(|err| err.into_inner());
+                while buffer.lines.is_empty() && buffer.dropped == 0 && !buffer.closed {
+                    buffer = available
+                        .wait(buffer)
+                        .unwrap_or_else(|err| err.into_inner());
+                }
+                let lines = std::mem::take(&mut buffer.lines);
+                let dropped = std::mem::take(&mut buffer.dropped);
+                let closed = buffer.closed;
+                drop(buffer);
+
+                // the terminal is written without holding the lock, so logging never waits for it
+                if dropped > 0 {
+                    let _ = writeln!(writer, "logtra: dropped {} console lines", dropped);
+                }
+                for line in lines {
+                    let _ = writer.write_all(line.as_bytes());
+                }
+                let _ = writer.flush();
+                if closed {
+                    return;
+                }
+            })?;
+
+        Ok(Self {
+            decl,
+            capacity,
+            buffer,
+            writer: Some(writer),
+        })
+    }
+}
+
+impl Sink for BufferedConsoleSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let rendered = msg.render(&self.decl);
+        let (lock, available) = &*self.buffer;
+        let mut buffer = lock.lock().unwrap_or_else(|err| err.into_inner());
+        if buffer.lines.len() >= self.capacity {
+            buffer.lines.pop_front();
+            buffer.dropped += 1;
+        }
+        buffer.lines.push_back(rendered);
+        available.notify_one();
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            color: true,
+            asynchronous: true,
+            ..Default::default()
+        }
+    }
+
+    decl_accessors!();
+}
+
+impl Drop for BufferedConsoleSink {
+    fn drop(&mut self) {
+        let (lock, available) = &*self.buffer;
+        lock.lock().unwrap_or_else(|err| err.into_inner()).closed = true;
+        available.notify_one();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
     }
 }
 
 const FILE_SINK_BUFFER_SIZE: usize = 1000;
+
+/// What a [crate::sink::FileSink] does with its buffered msgs when writing them fails, e.g. because the disk is full
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub enum FlushFailure {
+    /// Keeps the msgs and retries with the next msg, dropping and counting new msgs while the buffer is full
+    #[default]
+    Retain,
+    /// Drops and counts the buffered msgs
+    Discard,
+    /// Appends the buffered msgs to the fallback path instead, dropping and counting them if that fails as well
+    Spill(String),
+}
+
 pub struct FileSink {
     decl: SinkDeclaration,
+    template: CompiledTemplate,
+    /// Opened in append mode on the first flush and kept open afterwards
+    file: Option<File>,
     buffer: [String; FILE_SINK_BUFFER_SIZE],
     index: usize,
+    on_flush_failure: FlushFailure,
+    dropped: u64,
+    /// The last error of a flush triggered by logging, which has no caller to return it to
+    last_error: Option<Error>,
 }
 
 impl FileSink {
-    fn new(decl: SinkDeclaration) -> Self {
+    /// Creates a sink writing to the file named by the [crate::sink::SinkDeclaration]
+    pub fn new(decl: SinkDeclaration) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a file sink must be a path".to_string(),
+            ));
+        }
+        decl.validate()?;
+
         const EMPTY: String = String::new();
-        FileSink {
+        Ok(FileSink {
+            template: CompiledTemplate::new(&decl.template),
             decl,
+            file: None,
             buffer: [EMPTY; FILE_SINK_BUFFER_SIZE],
             index: 0,
-        }
+            on_flush_failure: FlushFailure::default(),
+            dropped: 0,
+            last_error: None,
+        })
+    }
+
+    /// Sets what happens to the buffered msgs when writing them fails, by default they are retained
+    pub fn with_flush_failure(mut self, on_flush_failure: FlushFailure) -> Self {
+        self.on_flush_failure = on_flush_failure;
+        self
+    }
+
+    /// Returns the number of msgs which were dropped because they could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the last error of writing the buffered msgs while logging, see [crate::sink::Sink::flush] to handle errors directly
+    pub fn last_error(&self) -> Option<&Error> {
+        self.last_error.as_ref()
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        let mut file = File::create(&self.decl.name)?;
-        for i in 0..self.index {
-            file.write_all((self.buffer.get(i).unwrap()).as_bytes())?;
+    fn clear(&mut self) {
+        // the lines keep their capacity to render the next msgs into
+        for line in self.buffer[..self.index].iter_mut() {
+            line.clear();
         }
+        self.index = 0;
+    }
 
-        const EMPTY: String = String::new();
-        self.buffer = [EMPTY; FILE_SINK_BUFFER_SIZE];
+    fn spill(&self, path: &str) -> Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        for line in self.buffer[..self.index].iter() {
+            file.write_all(line.as_bytes())?;
+        }
         Ok(())
     }
+
+    fn flush_or_recover(&mut self) {
+        match self.flush() {
+            Ok(()) => return,
+            Err(err) => self.last_error = Some(err),
+        }
+
+        match &self.on_flush_failure {
+            FlushFailure::Retain => return,
+            FlushFailure::Discard => self.dropped += self.index as u64,
+            FlushFailure::Spill(path) => {
+                if self.spill(path).is_err() {
+                    self.dropped += self.index as u64;
+                }
+            }
+        }
+        self.clear();
+    }
 }
 
 impl Sink for FileSink {
     fn log(&mut self, msg: &LogMessage) {
-        self.buffer[self.index] = msg.parse(&self.decl.template);
-        if self.index + 1 >= FILE_SINK_BUFFER_SIZE {
-            if let Err(err) = self.flush() {
-                panic!("{}", err);
+        // only a retained buffer can still be full
+        if self.index >= FILE_SINK_BUFFER_SIZE {
+            self.flush_or_recover();
+            if self.index >= FILE_SINK_BUFFER_SIZE {
+                self.dropped += 1;
+                return;
             }
         }
-        self.index = self.index + 1 % FILE_SINK_BUFFER_SIZE;
+
+        self.template.update(&self.decl.template);
+        let line = &mut self.buffer[self.index];
+        line.clear();
+        msg.render_compiled_into(&self.template, &self.decl, line);
+        self.index += 1;
+        if self.index >= FILE_SINK_BUFFER_SIZE || self.decl.flushes_on(msg.severity) {
+            self.flush_or_recover();
+        }
     }
 
-    fn severity(&self) -> LogSeverity {
-        self.decl.severity
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            flushable: true,
+            ..Default::default()
+        }
     }
 
-    fn module(&self) -> &str {
-        &self.decl.module
+    fn config(&self) -> Option<SinkConfigEntry> {
+        Some(SinkConfigEntry {
+            kind: SinkKind::File,
+            decl: self.decl.clone(),
+        })
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        self.flush()?;
+        // the file is opened by name again on the next flush
+        self.file = None;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.file.is_none() {
+            self.file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.decl.name)?,
+            );
+        }
+        let file = self.file.as_mut().unwrap();
+
+        for (written, line) in self.buffer[..self.index].iter().enumerate() {
+            if let Err(err) = file.write_all(line.as_bytes()) {
+                // keep only the msgs which were not written, so a retry does not write any twice
+                self.buffer[..self.index].rotate_left(written);
+                self.index -= written;
+                return Err(err.into());
+            }
+        }
+
+        self.clear();
+        Ok(())
     }
+
+    decl_accessors!();
 }
 
 impl Drop for FileSink {
     fn drop(&mut self) {
+        // panicking here would abort while unwinding
         if let Err(err) = self.flush() {
-            panic!("{}", err);
+            eprintln!("logtra: failed to flush {}: {}", self.decl.name, err);
         }
     }
 }
@@ -134,76 +775,4613 @@ impl Sink for VoidSink {
         // do nothing
     }
 
-    fn severity(&self) -> LogSeverity {
-        self.decl.severity
+    fn config(&self) -> Option<SinkConfigEntry> {
+        Some(SinkConfigEntry {
+            kind: SinkKind::Void,
+            decl: self.decl.clone(),
+        })
     }
 
-    fn module(&self) -> &str {
-        &self.decl.module
+    decl_accessors!();
+}
+
+/// Prints the whole [crate::msg::LogMessage] structure to stderr, ignoring the template
+/// Intended as an aid while developing custom sinks, to see which fields are populated.
+pub struct DebugSink {
+    decl: SinkDeclaration,
+}
+
+impl DebugSink {
+    pub fn new(decl: SinkDeclaration) -> Self {
+        Self { decl }
+    }
+
+    fn dump(msg: &LogMessage) -> String {
+        format!("{:#?}", msg)
     }
 }
 
-#[cfg(test)]
-mod test {
-    use std::{fs::remove_file, path::Path};
+impl Sink for DebugSink {
+    fn log(&mut self, msg: &LogMessage) {
+        eprintln!("{}", Self::dump(msg));
+    }
 
-    use chrono::{DateTime, Utc};
+    fn config(&self) -> Option<SinkConfigEntry> {
+        Some(SinkConfigEntry {
+            kind: SinkKind::Debug,
+            decl: self.decl.clone(),
+        })
+    }
 
-    use crate::{
-        msg::{Color, LogMessage, LogSeverity},
-        sink::{ConsoleSink, Sink, SinkDeclaration},
-    };
+    decl_accessors!();
+}
 
-    use super::FileSink;
+/// Writes every msg into `<name>/<first module segment>.log`, with the directory given as name of the [crate::sink::SinkDeclaration]
+/// Keeps at most `max_open_files` files open, closing the least recently used one first. Msgs which can not be written are dropped and counted.
+pub struct ShardedFileSink {
+    decl: SinkDeclaration,
+    max_open_files: usize,
+    files: HashMap<String, File>,
+    recently_used: VecDeque<String>,
+    dropped: u64,
+}
 
-    #[test]
-    fn console_sink_works() {
-        let decl = SinkDeclaration {
-            name: "Default".to_string(),
-            severity: LogSeverity::Info,
-            module: "".to_string(),
-            template: "[%t][%c%s%c][%f:%l]: %m\n".to_string(),
-        };
-        let msg = LogMessage {
-            time: DateTime::<Utc>::default().into(),
-            module: "logtra",
-            file: file!(),
-            line: line!(),
-            msg: "Hello world!",
-            severity: LogSeverity::Info,
-            color: Color::Red,
+impl ShardedFileSink {
+    pub fn new(decl: SinkDeclaration, max_open_files: usize) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a sharded file sink must be a directory".to_string(),
+            ));
+        }
+        if max_open_files == 0 {
+            return Err(Error::Config(
+                "a sharded file sink must be allowed to open at least one file".to_string(),
+            ));
+        }
+        decl.validate()?;
+        fs::create_dir_all(&decl.name)?;
+
+        Ok(Self {
+            decl,
+            max_open_files,
+            files: HashMap::new(),
+            recently_used: VecDeque::new(),
+            dropped: 0,
+        })
+    }
+
+    /// Returns the number of msgs which could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn shard(&mut self, shard: &str) -> std::io::Result<&mut File> {
+        if let Some(position) = self.recently_used.iter().position(|used| used == shard) {
+            self.recently_used.remove(position);
+        } else {
+            if self.files.len() >= self.max_open_files {
+                if let Some(evicted) = self.recently_used.pop_front() {
+                    self.files.remove(&evicted);
+                }
+            }
+            let path = std::path::Path::new(&self.decl.name).join(format!("{}.log", shard));
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            self.files.insert(shard.to_string(), file);
+        }
+        self.recently_used.push_back(shard.to_string());
+
+        Ok(self.files.get_mut(shard).unwrap())
+    }
+}
+
+impl Sink for ShardedFileSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let shard = match msg.module.split("::").next() {
+            Some(shard) if !shard.is_empty() => shard,
+            _ => "root",
         };
+        let rendered = msg.render(&self.decl);
+        let written = self
+            .shard(shard)
+            .and_then(|file| file.write_all(rendered.as_bytes()));
+        if written.is_err() {
+            self.dropped += 1;
+        }
+    }
 
-        let mut sink = ConsoleSink::new(decl);
-        sink.log(&msg);
+    fn reopen(&mut self) -> Result<()> {
+        // the shards are opened again on their next msg
+        self.files.clear();
+        self.recently_used.clear();
+        Ok(())
     }
 
-    #[test]
+    decl_accessors!();
+}
+
+/// Writes every msg into the file named by the [crate::sink::SinkDeclaration], rotating it once it would exceed `max_bytes`
+/// The full file is renamed to `<name>.1`, the previous `<name>.1` to `<name>.2` and so on, keeping at most `max_files` rotated files.
+/// Every rename replaces its target at once, so a rotated file is never half written.
+/// `<name>` itself is missing between renaming it and creating it again for the msg which caused the rotation.
+/// A msg larger than `max_bytes` is still written, into a file of its own. Msgs which can not be written are dropped and counted.
+/// With [crate::sink::RollingFileSink::with_compression] every rotated file is gzipped into `<name>.1.gz` on a thread of its own,
+/// so rotating stays fast. `<name>` itself is never compressed and can still be tailed.
+/// With [crate::sink::RollingFileSink::with_max_age] rotated files last modified longer ago are deleted on every rotation.
+pub struct RollingFileSink {
+    decl: SinkDeclaration,
+    max_bytes: u64,
+    max_files: usize,
+    max_age: Option<std::time::Duration>,
+    /// Opened in append mode on the first msg and after every rotation
+    file: Option<File>,
+    size: u64,
+    dropped: u64,
+    #[cfg(feature = "flate2")]
+    compress: bool,
+    /// Compresses the most recently rotated file, finished before the next rotation
+    #[cfg(feature = "flate2")]
+    compression: Option<JoinHandle<()>>,
+}
+
+/// The suffixes of the files rotated by a [crate::sink::RollingFileSink], uncompressed and compressed
+const ROTATED_SUFFIXES: [&str; 2] = ["", ".gz"];
+
+/// Compresses the rotated file into `<path>.gz` and removes it, the uncompressed file is kept if that fails
+#[cfg(feature = "flate2")]
+fn gzip_rotated(path: &str) -> std::io::Result<()> {
+    let partial = format!("{}.gz.partial", path);
+    let compressed = File::create(&partial).and_then(|file| {
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        std::io::copy(&mut File::open(path)?, &mut encoder)?;
+        encoder.finish()?.sync_all()
+    });
+    if let Err(err) = compressed {
+        let _ = fs::remove_file(&partial);
+        return Err(err);
+    }
+    fs::rename(&partial, format!("{}.gz", path))?;
+    fs::remove_file(path)
+}
+
+impl RollingFileSink {
+    pub fn new(decl: SinkDeclaration, max_bytes: u64, max_files: usize) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a rolling file sink must be a path".to_string(),
+            ));
+        }
+        if max_bytes == 0 {
+            return Err(Error::Config(
+                "a rolling file sink must allow its files to hold at least one byte".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        Ok(Self {
+            decl,
+            max_bytes,
+            max_files,
+            max_age: None,
+            file: None,
+            size: 0,
+            dropped: 0,
+            #[cfg(feature = "flate2")]
+            compress: false,
+            #[cfg(feature = "flate2")]
+            compression: None,
+        })
+    }
+
+    /// Gzips every rotated file into `<name>.<index>.gz`
+    #[cfg(feature = "flate2")]
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Deletes rotated files which were last modified longer ago than `max_age` on every rotation
+    pub fn with_max_age(mut self, max_age: std::time::Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns the number of msgs which could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Deletes the rotated files last modified longer ago than `max_age`
+    fn purge(&self, max_age: std::time::Duration) {
+        let now = std::time::SystemTime::now();
+        for index in 1..=self.max_files {
+            for suffix in ROTATED_SUFFIXES {
+                let path = format!("{}.{}{}", self.decl.name, index, suffix);
+                let expired = fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| {
+                        now.duration_since(modified).unwrap_or_default() > max_age
+                    });
+                if expired {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+    }
+
+    /// Waits until the most recently rotated file is compressed
+    fn finish_compression(&mut self) {
+        #[cfg(feature = "flate2")]
+        if let Some(compression) = self.compression.take() {
+            let _ = compression.join();
+        }
+    }
+
+    /// Compresses the most recently rotated file on a thread of its own
+    #[cfg(feature = "flate2")]
+    fn compress_rotated(&mut self) {
+        let path = format!("{}.1", self.decl.name);
+        let compression = std::thread::Builder::new()
+            .name("logtra-gzip".to_string())
+            .spawn(move || {
+                if let Err(err) = gzip_rotated(&path) {
+                    eprintln!("logtra: failed to compress {}: {}", path, err);
+                }
+            });
+        match compression {
+            Ok(compression) => self.compression = Some(compression),
+            Err(err) => eprintln!("logtra: failed to compress {}.1: {}", self.decl.name, err),
+        }
+    }
+
+    fn open(&mut self) -> std::io::Result<&mut File> {
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.decl.name)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        // the file being compressed is renamed below
+        self.finish_compression();
+        if self.max_files == 0 {
+            return fs::remove_file(&self.decl.name);
+        }
+
+        // the oldest file is replaced by the one before it, whether either is compressed or not
+        for suffix in ROTATED_SUFFIXES {
+            let oldest = format!("{}.{}{}", self.decl.name, self.max_files, suffix);
+            match fs::remove_file(oldest) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => (),
+            }
+            for index in (1..self.max_files).rev() {
+                let from = format!("{}.{}{}", self.decl.name, index, suffix);
+                let to = format!("{}.{}{}", self.decl.name, index + 1, suffix);
+                match fs::rename(from, to) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                    _ => (),
+                }
+            }
+        }
+        fs::rename(&self.decl.name, format!("{}.1", self.decl.name))?;
+        if let Some(max_age) = self.max_age {
+            self.purge(max_age);
+        }
+
+        #[cfg(feature = "flate2")]
+        if self.compress {
+            self.compress_rotated();
+        }
+        Ok(())
+    }
+}
+
+impl Sink for RollingFileSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let rendered = msg.render(&self.decl);
+        if self.open().is_err() {
+            self.dropped += 1;
+            return;
+        }
+
+        // an empty file is never rotated, which is where an oversized msg ends up
+        if self.size > 0 && self.size + rendered.len() as u64 > self.max_bytes {
+            // keep writing into the full file rather than losing the msg
+            let _ = self.rotate();
+        }
+        let written = self
+            .open()
+            .and_then(|file| file.write_all(rendered.as_bytes()));
+        match written {
+            Ok(()) => self.size += rendered.len() as u64,
+            Err(_) => self.dropped += 1,
+        }
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        // the file is opened by name again on the next msg
+        self.file = None;
+        Ok(())
+    }
+
+    /// Waits until the most recently rotated file is compressed
+    fn flush(&mut self) -> Result<()> {
+        self.finish_compression();
+        Ok(())
+    }
+
+    decl_accessors!();
+}
+
+impl Drop for RollingFileSink {
+    fn drop(&mut self) {
+        self.finish_compression();
+    }
+}
+
+/// How often a [crate::sink::TimedRollingFileSink] starts a new file
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum RollInterval {
+    Hourly,
+    Daily,
+}
+
+impl RollInterval {
+    fn seconds(&self) -> i64 {
+        match self {
+            RollInterval::Hourly => 60 * 60,
+            RollInterval::Daily => 24 * 60 * 60,
+        }
+    }
+}
+
+/// Writes every msg into a file named after the UTC time of the msg, e.g. `app-%Y-%m-%d.log` with strftime patterns
+/// Starts a new file whenever a msg falls into another interval than the previous one. Msgs which can not be written are dropped and counted.
+/// With [crate::sink::TimedRollingFileSink::with_max_age] the files of intervals which ended longer ago are deleted whenever a new file is started.
+/// Their interval is parsed from their name, so only files in the directory of the pattern whose name matches the pattern are deleted.
+pub struct TimedRollingFileSink {
+    decl: SinkDeclaration,
+    interval: RollInterval,
+    max_age: Option<std::time::Duration>,
+    /// The interval the open file belongs to, counted since the epoch
+    period: i64,
+    file: Option<File>,
+    dropped: u64,
+}
+
+impl TimedRollingFileSink {
+    pub fn new(decl: SinkDeclaration, interval: RollInterval) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a timed rolling file sink must be a path".to_string(),
+            ));
+        }
+        if chrono::format::StrftimeItems::new(&decl.name)
+            .any(|item| item == chrono::format::Item::Error)
+        {
+            return Err(Error::Config(format!(
+                "the path {} contains an unknown strftime pattern",
+                decl.name
+            )));
+        }
+        decl.validate()?;
+
+        Ok(Self {
+            decl,
+            interval,
+            max_age: None,
+            period: 0,
+            file: None,
+            dropped: 0,
+        })
+    }
+
+    /// Returns the number of msgs which could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Deletes the files of intervals which ended longer ago than `max_age` whenever a new file is started
+    pub fn with_max_age(mut self, max_age: std::time::Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    fn open(&mut self, time: chrono::DateTime<chrono::Utc>) -> std::io::Result<&mut File> {
+        let period = time.timestamp().div_euclid(self.interval.seconds());
+        if self.file.is_none() || period != self.period {
+            // the previous file is closed before the next one is opened
+            self.file = None;
+            let path = time.format(&self.decl.name).to_string();
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            self.file = Some(file);
+            self.period = period;
+            if let Some(max_age) = self.max_age {
+                self.purge(time, &path, max_age);
+            }
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    /// Deletes the files of intervals which ended longer ago than `max_age` before the given time, except the open one
+    fn purge(&self, time: chrono::DateTime<chrono::Utc>, open: &str, max_age: std::time::Duration) {
+        let pattern = std::path::Path::new(&self.decl.name);
+        let Some(name_pattern) = pattern.file_name().and_then(|name| name.to_str()) else {
+            return;
+        };
+        let directory = match pattern.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory,
+            _ => std::path::Path::new("."),
+        };
+        let Ok(entries) = fs::read_dir(directory) else {
+            return;
+        };
+        let max_age = chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+
+        for entry in entries.flatten() {
+            let Some(started) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| interval_start(name, name_pattern))
+            else {
+                continue;
+            };
+            let ended = started + chrono::Duration::seconds(self.interval.seconds());
+            let path = entry.path();
+            if path != std::path::Path::new(open) && time.signed_duration_since(ended) > max_age {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Parses the UTC time a file was named after with the strftime pattern, e.g. `app-2023-03-01.log` with `app-%Y-%m-%d.log`
+/// Missing hours, minutes and seconds count as 0.
+fn interval_start(name: &str, pattern: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let mut parsed = chrono::format::Parsed::new();
+    chrono::format::parse(
+        &mut parsed,
+        name,
+        chrono::format::StrftimeItems::new(pattern),
+    )
+    .ok()?;
+    // fails if the pattern named the field already
+    let _ = parsed.set_hour(0);
+    let _ = parsed.set_minute(0);
+    let _ = parsed.set_second(0);
+    let start = parsed
+        .to_naive_date()
+        .ok()?
+        .and_time(parsed.to_naive_time().ok()?);
+    Some(start.and_utc())
+}
+
+impl Sink for TimedRollingFileSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let rendered = msg.render(&self.decl);
+        let written = self
+            .open(msg.time.into())
+            .and_then(|file| file.write_all(rendered.as_bytes()));
+        if written.is_err() {
+            self.dropped += 1;
+        }
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        // the file is opened by name again on the next msg
+        self.file = None;
+        Ok(())
+    }
+
+    decl_accessors!();
+}
+
+/// Passes every msg to a closure, e.g. for throwaway sinks while prototyping, see [crate::sink_fn!]
+pub struct FnSink<F> {
+    decl: SinkDeclaration,
+    f: F,
+}
+
+impl<F: FnMut(&LogMessage) + Send + 'static> FnSink<F> {
+    pub fn new(decl: SinkDeclaration, f: F) -> Self {
+        Self { decl, f }
+    }
+}
+
+impl<F: FnMut(&LogMessage) + Send + 'static> Sink for FnSink<F> {
+    fn log(&mut self, msg: &LogMessage) {
+        (self.f)(msg);
+    }
+
+    decl_accessors!();
+}
+
+/// Writes every rendered msg into any writer, e.g. a pipe, a socket or a `Vec<u8>` in tests
+/// Msgs which can not be written are dropped and counted.
+pub struct WriterSink<W> {
+    decl: SinkDeclaration,
+    writer: W,
+    dropped: u64,
+}
+
+impl<W: Write + Send + 'static> WriterSink<W> {
+    pub fn new(decl: SinkDeclaration, writer: W) -> Self {
+        Self {
+            decl,
+            writer,
+            dropped: 0,
+        }
+    }
+
+    /// Returns the writer, e.g. to inspect a `Vec<u8>` in tests
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the number of msgs which could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl<W: Write + Send + 'static> Sink for WriterSink<W> {
+    fn log(&mut self, msg: &LogMessage) {
+        if self
+            .writer
+            .write_all(msg.render(&self.decl).as_bytes())
+            .is_err()
+        {
+            self.dropped += 1;
+        }
+    }
+
+    decl_accessors!();
+}
+
+/// Writes every rendered msg into any [std::fmt::Write], e.g. a `String` or the UART of a board without files
+/// Renders into a reused buffer. Msgs which can not be written are dropped and counted.
+pub struct FmtWriterSink<W> {
+    decl: SinkDeclaration,
+    writer: W,
+    buffer: String,
+    dropped: u64,
+}
+
+impl<W: std::fmt::Write + Send + 'static> FmtWriterSink<W> {
+    pub fn new(decl: SinkDeclaration, writer: W) -> Self {
+        Self {
+            decl,
+            writer,
+            buffer: String::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Returns the writer, e.g. to inspect a `String` in tests
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the number of msgs which could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl<W: std::fmt::Write + Send + 'static> Sink for FmtWriterSink<W> {
+    fn log(&mut self, msg: &LogMessage) {
+        self.buffer.clear();
+        msg.render_into(&self.decl, &mut self.buffer);
+        if self.writer.write_str(&self.buffer).is_err() {
+            self.dropped += 1;
+        }
+    }
+
+    decl_accessors!();
+}
+
+/// Writes every msg as a JSON object on a line of its own into any writer, ignoring the template, e.g. for Elasticsearch or Loki
+/// See [crate::msg::LogMessage::render_json] for the fields. Msgs which can not be written are dropped and counted.
+/// With [crate::sink::JsonSink::with_array_batches] the objects are collected instead and written as one JSON array per flush,
+/// e.g. `[{...},{...}]` for backends which expect an array per request.
+pub struct JsonSink<W: Write> {
+    decl: SinkDeclaration,
+    writer: W,
+    /// The number of objects per array, or `None` to write a line per msg
+    batch_size: Option<usize>,
+    batch: Vec<String>,
+    dropped: u64,
+}
+
+impl<W: Write + Send + 'static> JsonSink<W> {
+    pub fn new(decl: SinkDeclaration, writer: W) -> Self {
+        Self {
+            decl,
+            writer,
+            batch_size: None,
+            batch: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Writes the msgs as one JSON array on a line of its own once `batch_size` msgs are collected,
+    /// a msg of the flush severity is logged or the sink is flushed
+    pub fn with_array_batches(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size.max(1));
+        self
+    }
+
+    /// Returns the writer, e.g. to inspect a `Vec<u8>` in tests
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the number of msgs which could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl<W: Write + Send + 'static> Sink for JsonSink<W> {
+    fn log(&mut self, msg: &LogMessage) {
+        let object = msg.render_json(&self.decl);
+        let Some(batch_size) = self.batch_size else {
+            let mut line = object;
+            line.push('\n');
+            if self.writer.write_all(line.as_bytes()).is_err() {
+                self.dropped += 1;
+            }
+            return;
+        };
+
+        self.batch.push(object);
+        if self.batch.len() >= batch_size || self.decl.flushes_on(msg.severity) {
+            let _ = self.flush();
+        }
+    }
+
+    /// Writes the collected msgs as one JSON array
+    fn flush(&mut self) -> Result<()> {
+        if !self.batch.is_empty() {
+            let written = self.writer.write_all(json_array(&self.batch).as_bytes());
+            let batched = std::mem::take(&mut self.batch).len() as u64;
+            if let Err(err) = written {
+                self.dropped += batched;
+                return Err(err.into());
+            }
+        }
+        Ok(self.writer.flush()?)
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            structured_fields: true,
+            flushable: self.batch_size.is_some(),
+            ..Default::default()
+        }
+    }
+
+    decl_accessors!();
+}
+
+/// Joins the JSON objects into an array on a line of its own
+fn json_array(objects: &[String]) -> String {
+    format!("[{}]\n", objects.join(","))
+}
+
+impl<W: Write> Drop for JsonSink<W> {
+    fn drop(&mut self) {
+        if !self.batch.is_empty()
+            && self
+                .writer
+                .write_all(json_array(&self.batch).as_bytes())
+                .is_err()
+        {
+            eprintln!(
+                "logtra: failed to write {} msgs of {}",
+                self.batch.len(),
+                self.decl.name
+            );
+        }
+    }
+}
+
+/// Keeps every rendered msg in memory, e.g. to assert on logged lines in tests
+/// Clones share the rendered lines, so one clone can be registered via [crate::sink!] and another one asserted on.
+#[derive(Clone)]
+pub struct MemorySink {
+    decl: SinkDeclaration,
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+/// A [crate::sink::MemorySink] under the name it is usually registered by in tests, to capture the logged lines
+pub type CaptureSink = MemorySink;
+
+impl MemorySink {
+    pub fn new(decl: SinkDeclaration) -> Self {
+        Self {
+            decl,
+            lines: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns a handle to the rendered lines, which stays valid after the sink is registered via [crate::sink!]
+    pub fn lines(&self) -> Arc<Mutex<Vec<String>>> {
+        self.lines.clone()
+    }
+
+    /// Returns the lines rendered so far, oldest first
+    pub fn messages(&self) -> Vec<String> {
+        self.lines
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Forgets every rendered line
+    pub fn clear(&self) {
+        self.lines
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+}
+
+impl Sink for MemorySink {
+    fn log(&mut self, msg: &LogMessage) {
+        self.lines
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(msg.render(&self.decl));
+    }
+
+    decl_accessors!();
+}
+
+/// Renders every msg like a writing sink would, but only counts them
+/// Unlike the [crate::sink::VoidSink] this includes the cost of rendering, e.g. for benchmarks
+pub struct CountingRenderSink {
+    decl: SinkDeclaration,
+    template: CompiledTemplate,
+    buffer: String,
+    count: Arc<AtomicUsize>,
+}
+
+impl CountingRenderSink {
+    pub fn new(decl: SinkDeclaration) -> Self {
+        Self {
+            template: CompiledTemplate::new(&decl.template),
+            decl,
+            buffer: String::new(),
+            count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns a handle to the number of rendered msgs, which stays valid after the sink is registered via [crate::sink!]
+    pub fn counter(&self) -> Arc<AtomicUsize> {
+        self.count.clone()
+    }
+}
+
+impl Sink for CountingRenderSink {
+    fn log(&mut self, msg: &LogMessage) {
+        self.template.update(&self.decl.template);
+        self.buffer.clear();
+        msg.render_compiled_into(&self.template, &self.decl, &mut self.buffer);
+        std::hint::black_box(&self.buffer);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    decl_accessors!();
+}
+
+/// How a socket sink delimits the rendered msgs it sends
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Framing {
+    /// Terminates every msg with a newline, unless the template already does
+    Newline,
+    /// Prefixes every msg with its length in bytes as a big endian u32
+    LengthPrefixed,
+}
+
+impl Framing {
+    fn frame(&self, rendered: String) -> Vec<u8> {
+        match self {
+            Framing::Newline => {
+                let mut framed = rendered.into_bytes();
+                if framed.last() != Some(&b'\n') {
+                    framed.push(b'\n');
+                }
+                framed
+            }
+            Framing::LengthPrefixed => {
+                let mut framed = (rendered.len() as u32).to_be_bytes().to_vec();
+                framed.extend_from_slice(rendered.as_bytes());
+                framed
+            }
+        }
+    }
+}
+
+/// Ends every record but the last one of a split msg, see [crate::sink::UnixSocketSink::with_max_record_size]
+pub const CONTINUATION_MARKER: &str = "\\";
+
+/// Splits a rendered msg into records of at most `max_bytes`, never inside a UTF-8 character
+/// Every record but the last one ends with the [crate::sink::CONTINUATION_MARKER], which is counted towards its size.
+/// A limit too small for the marker and a character still puts one character into every record.
+/// Removing the markers and joining the records restores the msg.
+fn split_records(rendered: String, max_bytes: usize) -> Vec<String> {
+    if rendered.len() <= max_bytes {
+        return vec![rendered];
+    }
+
+    let limit = max_bytes.saturating_sub(CONTINUATION_MARKER.len());
+    let mut records = Vec::new();
+    let mut rest = rendered.as_str();
+    while rest.len() > max_bytes {
+        let mut end = limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        // a limit too small for a single character would never make progress
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        records.push(format!("{}{}", &rest[..end], CONTINUATION_MARKER));
+        rest = &rest[end..];
+    }
+    records.push(rest.to_string());
+    records
+}
+
+/// Whether a [crate::sink::UnixSocketSink] connects to a stream or a datagram socket
+#[cfg(unix)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum UnixSocketKind {
+    Stream,
+    Datagram,
+}
+
+#[cfg(unix)]
+enum UnixConnection {
+    Stream(std::os::unix::net::UnixStream),
+    Datagram(std::os::unix::net::UnixDatagram),
+}
+
+/// Sends every rendered msg to the Unix domain socket at the path named by the [crate::sink::SinkDeclaration]
+/// Connects lazily and reconnects on the next msg after a failed write. Msgs which can not be sent are dropped and counted.
+/// A write which exceeds the write timeout counts as failed, so a stalled reader can not block the logging thread.
+#[cfg(unix)]
+pub struct UnixSocketSink {
+    decl: SinkDeclaration,
+    kind: UnixSocketKind,
+    framing: Framing,
+    write_timeout: Option<std::time::Duration>,
+    max_record_size: Option<usize>,
+    connection: Option<UnixConnection>,
+    dropped: u64,
+}
+
+#[cfg(unix)]
+impl UnixSocketSink {
+    pub fn new(decl: SinkDeclaration, kind: UnixSocketKind, framing: Framing) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a unix socket sink must be a socket path".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        Ok(Self {
+            decl,
+            kind,
+            framing,
+            write_timeout: None,
+            max_record_size: None,
+            connection: None,
+            dropped: 0,
+        })
+    }
+
+    /// Fails writes which block longer than the timeout, by default writes block indefinitely
+    pub fn with_write_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Splits msgs longer than `max_bytes` into several records instead of sending them at once, e.g. for datagram size limits
+    /// The size excludes the framing, every record is framed on its own. See [crate::sink::CONTINUATION_MARKER].
+    pub fn with_max_record_size(mut self, max_bytes: usize) -> Self {
+        self.max_record_size = Some(max_bytes);
+        self
+    }
+
+    /// Returns the number of msgs which could not be sent
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn connect(&self) -> std::io::Result<UnixConnection> {
+        use std::os::unix::net::{UnixDatagram, UnixStream};
+
+        match self.kind {
+            UnixSocketKind::Stream => {
+                let stream = UnixStream::connect(&self.decl.name)?;
+                stream.set_write_timeout(self.write_timeout)?;
+                Ok(UnixConnection::Stream(stream))
+            }
+            UnixSocketKind::Datagram => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(&self.decl.name)?;
+                socket.set_write_timeout(self.write_timeout)?;
+                Ok(UnixConnection::Datagram(socket))
+            }
+        }
+    }
+
+    fn send(&mut self, framed: &[u8]) -> std::io::Result<()> {
+        if self.connection.is_none() {
+            self.connection = Some(self.connect()?);
+        }
+
+        match self.connection.as_mut() {
+            Some(UnixConnection::Stream(stream)) => stream.write_all(framed),
+            Some(UnixConnection::Datagram(socket)) => socket.send(framed).map(|_| ()),
+            None => unreachable!(),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Sink for UnixSocketSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let rendered = msg.render(&self.decl);
+        let records = match self.max_record_size {
+            Some(max_bytes) => split_records(rendered, max_bytes),
+            None => vec![rendered],
+        };
+        for record in records {
+            let framed = self.framing.frame(record);
+            if self.send(&framed).is_err() {
+                // the rest of a split msg is useless without this record
+                self.connection = None;
+                self.dropped += 1;
+                return;
+            }
+        }
+    }
+
+    decl_accessors!();
+}
+
+/// The first delay before a [crate::sink::TcpSink] reconnects, doubled after every failed attempt
+const TCP_SINK_MIN_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+const TCP_SINK_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Sends every rendered msg as a line to the `host:port` named by the [crate::sink::SinkDeclaration]
+/// Keeps at most `capacity` lines while the peer is unreachable and sends them once it is reachable again,
+/// dropping and counting the oldest line when full. Reconnects on a later msg, waiting longer after every failed attempt.
+/// Connecting and writing time out after a second by default, so an unreachable peer never blocks the logging thread for long.
+pub struct TcpSink {
+    decl: SinkDeclaration,
+    capacity: usize,
+    timeout: std::time::Duration,
+    pending: VecDeque<Vec<u8>>,
+    connection: Option<std::net::TcpStream>,
+    backoff: std::time::Duration,
+    next_attempt: Option<std::time::Instant>,
+    dropped: u64,
+}
+
+impl TcpSink {
+    pub fn new(decl: SinkDeclaration, capacity: usize) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a tcp sink must be a host:port address".to_string(),
+            ));
+        }
+        if capacity == 0 {
+            return Err(Error::Config(
+                "a tcp sink must be allowed to hold at least one line".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        Ok(Self {
+            decl,
+            capacity,
+            timeout: std::time::Duration::from_secs(1),
+            pending: VecDeque::new(),
+            connection: None,
+            backoff: TCP_SINK_MIN_BACKOFF,
+            next_attempt: None,
+            dropped: 0,
+        })
+    }
+
+    /// Sets how long connecting and every write may block
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the number of lines which were dropped because the buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the number of lines waiting for the peer
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn connect(&self) -> std::io::Result<std::net::TcpStream> {
+        use std::net::{TcpStream, ToSocketAddrs};
+
+        let mut last_err = None;
+        for address in self.decl.name.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, self.timeout) {
+                Ok(stream) => {
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} resolves to no address", self.decl.name),
+            )
+        }))
+    }
+
+    /// Sends the pending lines, connecting first unless a failed attempt was too recent
+    fn send_pending(&mut self, force: bool) -> std::io::Result<()> {
+        if self.connection.is_none() {
+            let now = std::time::Instant::now();
+            if !force && self.next_attempt.is_some_and(|next| now < next) {
+                return Err(std::io::ErrorKind::NotConnected.into());
+            }
+            match self.connect() {
+                Ok(stream) => {
+                    self.connection = Some(stream);
+                    self.backoff = TCP_SINK_MIN_BACKOFF;
+                    self.next_attempt = None;
+                }
+                Err(err) => {
+                    self.next_attempt = Some(now + self.backoff);
+                    self.backoff = (self.backoff * 2).min(TCP_SINK_MAX_BACKOFF);
+                    return Err(err);
+                }
+            }
+        }
+
+        let stream = self.connection.as_mut().unwrap();
+        while let Some(line) = self.pending.front() {
+            if let Err(err) = stream.write_all(line) {
+                // the line is sent again after reconnecting
+                self.connection = None;
+                return Err(err);
+            }
+            self.pending.pop_front();
+        }
+        Ok(())
+    }
+}
+
+impl Sink for TcpSink {
+    fn log(&mut self, msg: &LogMessage) {
+        if self.pending.len() >= self.capacity {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending
+            .push_back(Framing::Newline.frame(msg.render(&self.decl)));
+        let _ = self.send_pending(false);
+    }
+
+    /// Sends every pending line, connecting right away even if a failed attempt was recent
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.send_pending(true)?)
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        // connects again on the next msg
+        self.connection = None;
+        self.next_attempt = None;
+        Ok(())
+    }
+
+    decl_accessors!();
+}
+
+impl Drop for TcpSink {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() && self.send_pending(true).is_err() {
+            eprintln!(
+                "logtra: failed to send {} lines to {}",
+                self.pending.len(),
+                self.decl.name
+            );
+        }
+    }
+}
+
+/// Marks a datagram of a [crate::sink::UdpSink] which was cut off at the MTU
+const UDP_SINK_ELLIPSIS: &str = "...";
+
+/// Sends every rendered msg as a single datagram to the `host:port` named by the [crate::sink::SinkDeclaration]
+/// Never blocks: a msg which can not be sent right away is dropped and counted.
+/// Msgs longer than the MTU, 1472 bytes by default, are cut off at a character boundary and end with `...`.
+pub struct UdpSink {
+    decl: SinkDeclaration,
+    socket: std::net::UdpSocket,
+    destination: std::net::SocketAddr,
+    mtu: usize,
+    dropped: u64,
+}
+
+impl UdpSink {
+    pub fn new(decl: SinkDeclaration) -> Result<Self> {
+        use std::net::{ToSocketAddrs, UdpSocket};
+
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of an udp sink must be a host:port address".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        let destination = decl.name.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::Config(format!("the address {} resolves to no address", decl.name))
+        })?;
+        let socket = match destination.is_ipv4() {
+            true => UdpSocket::bind("0.0.0.0:0")?,
+            false => UdpSocket::bind("[::]:0")?,
+        };
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            decl,
+            socket,
+            destination,
+            mtu: 1472,
+            dropped: 0,
+        })
+    }
+
+    /// Sets the size in bytes a datagram is cut off at
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu.max(UDP_SINK_ELLIPSIS.len() + 4);
+        self
+    }
+
+    /// Returns the number of msgs which could not be sent
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Cuts the rendered msg off at a character boundary, so it fits into `max_bytes` together with the ellipsis
+fn truncate_with_ellipsis(mut rendered: String, max_bytes: usize) -> String {
+    if rendered.len() <= max_bytes {
+        return rendered;
+    }
+
+    let mut end = max_bytes.saturating_sub(UDP_SINK_ELLIPSIS.len());
+    while !rendered.is_char_boundary(end) {
+        end -= 1;
+    }
+    rendered.truncate(end);
+    rendered.push_str(UDP_SINK_ELLIPSIS);
+    rendered
+}
+
+impl Sink for UdpSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let datagram = truncate_with_ellipsis(msg.render(&self.decl), self.mtu);
+        if self
+            .socket
+            .send_to(datagram.as_bytes(), self.destination)
+            .is_err()
+        {
+            self.dropped += 1;
+        }
+    }
+
+    decl_accessors!();
+}
+
+/// The facility a [crate::sink::SyslogSink] reports its msgs under
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SyslogFacility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Maps the severity to the syslog severity number, Verbose and Trace count as debug
+fn syslog_severity(severity: LogSeverity) -> u8 {
+    match severity {
+        LogSeverity::Fatal => 2,
+        LogSeverity::Error => 3,
+        LogSeverity::Warn => 4,
+        LogSeverity::Info => 6,
+        LogSeverity::Debug | LogSeverity::Trace | LogSeverity::Verbose => 7,
+    }
+}
+
+/// The PRI of a syslog msg, `facility * 8 + severity`
+fn syslog_priority(facility: SyslogFacility, severity: LogSeverity) -> u8 {
+    facility as u8 * 8 + syslog_severity(severity)
+}
+
+/// Replaces what RFC 5424 forbids in a header field and cuts it off at `max_len`, an empty field becomes `-`
+fn syslog_header_field(value: &str, max_len: usize) -> String {
+    let field: String = value
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(max_len)
+        .collect();
+    match field.is_empty() {
+        true => "-".to_string(),
+        false => field,
+    }
+}
+
+/// The name of this host as far as it can be found out without a system call, `-` otherwise
+fn hostname() -> String {
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .unwrap_or_default();
+    syslog_header_field(hostname.trim(), 255)
+}
+
+enum SyslogConnection {
+    Udp(std::net::UdpSocket),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixDatagram),
+}
+
+/// Sends every msg as a RFC 5424 syslog msg, either via UDP or to a local datagram socket like `/dev/log`
+/// The rendered msg follows a header of priority, timestamp, hostname, app name and pid.
+/// Never blocks: a msg which can not be sent right away is dropped and counted.
+pub struct SyslogSink {
+    decl: SinkDeclaration,
+    connection: SyslogConnection,
+    facility: SyslogFacility,
+    hostname: String,
+    app_name: String,
+    dropped: u64,
+}
+
+impl SyslogSink {
+    /// Sends to the syslog server at the `host:port` named by the [crate::sink::SinkDeclaration]
+    pub fn udp(decl: SinkDeclaration, facility: SyslogFacility) -> Result<Self> {
+        use std::net::{ToSocketAddrs, UdpSocket};
+
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of an udp syslog sink must be a host:port address".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        let destination = decl.name.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::Config(format!("the address {} resolves to no address", decl.name))
+        })?;
+        let socket = match destination.is_ipv4() {
+            true => UdpSocket::bind("0.0.0.0:0")?,
+            false => UdpSocket::bind("[::]:0")?,
+        };
+        socket.connect(destination)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self::with_connection(
+            decl,
+            SyslogConnection::Udp(socket),
+            facility,
+        ))
+    }
+
+    /// Sends to the local datagram socket at the path named by the [crate::sink::SinkDeclaration], usually `/dev/log`
+    #[cfg(unix)]
+    pub fn unix(decl: SinkDeclaration, facility: SyslogFacility) -> Result<Self> {
+        use std::os::unix::net::UnixDatagram;
+
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a unix syslog sink must be a socket path".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(&decl.name)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self::with_connection(
+            decl,
+            SyslogConnection::Unix(socket),
+            facility,
+        ))
+    }
+
+    fn with_connection(
+        decl: SinkDeclaration,
+        connection: SyslogConnection,
+        facility: SyslogFacility,
+    ) -> Self {
+        let app_name = std::env::current_exe()
+            .ok()
+            .and_then(|exe| {
+                exe.file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+
+        Self {
+            decl,
+            connection,
+            facility,
+            hostname: hostname(),
+            app_name: syslog_header_field(&app_name, 48),
+            dropped: 0,
+        }
+    }
+
+    /// Reports the msgs under the app name instead of the name of the executable
+    pub fn with_app_name(mut self, app_name: &str) -> Self {
+        self.app_name = syslog_header_field(app_name, 48);
+        self
+    }
+
+    /// Returns the number of msgs which could not be sent
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn format(&self, msg: &LogMessage) -> String {
+        let timestamp = chrono::DateTime::<chrono::Utc>::from(msg.time)
+            .to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+        format!(
+            "<{}>1 {} {} {} {} - - {}",
+            syslog_priority(self.facility, msg.severity),
+            timestamp,
+            self.hostname,
+            self.app_name,
+            std::process::id(),
+            msg.render(&self.decl)
+        )
+    }
+}
+
+impl Sink for SyslogSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let formatted = self.format(msg);
+        let sent = match &self.connection {
+            SyslogConnection::Udp(socket) => socket.send(formatted.as_bytes()),
+            #[cfg(unix)]
+            SyslogConnection::Unix(socket) => socket.send(formatted.as_bytes()),
+        };
+        if sent.is_err() {
+            self.dropped += 1;
+        }
+    }
+
+    decl_accessors!();
+}
+
+/// Splits an `http://host[:port][/path]` url into the `host:port` to connect to and the path, defaulting the port and path
+fn http_endpoint(url: &str, default_port: u16, default_path: &str) -> Result<(String, String)> {
+    let Some(endpoint) = url.strip_prefix("http://") else {
+        return Err(Error::Config(format!("{} is not an http:// url", url)));
+    };
+    let (host, path) = match endpoint.find('/') {
+        Some(slash) => (&endpoint[..slash], &endpoint[slash..]),
+        None => (endpoint, default_path),
+    };
+    if host.is_empty() {
+        return Err(Error::Config(format!("the url {} names no host", url)));
+    }
+    let host = match host.contains(':') && !host.ends_with(']') {
+        true => host.to_string(),
+        false => format!("{}:{}", host, default_port),
+    };
+    Ok((host, path.to_string()))
+}
+
+/// POSTs the JSON body to the path on `host:port`, failing unless the server answers with a 2xx status
+fn http_post(
+    host: &str,
+    path: &str,
+    body: &str,
+    timeout: std::time::Duration,
+) -> std::io::Result<()> {
+    use std::{
+        io::{BufRead, BufReader},
+        net::{TcpStream, ToSocketAddrs},
+    };
+
+    let address = host.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} resolves to no address", host),
+        )
+    })?;
+    let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.set_read_timeout(Some(timeout))?;
+
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    match status_line
+        .split(' ')
+        .nth(1)
+        .map(|status| status.starts_with('2'))
+    {
+        Some(true) => Ok(()),
+        _ => Err(std::io::Error::other(format!(
+            "the server answered {}",
+            status_line.trim_end()
+        ))),
+    }
+}
+
+/// How long a [crate::sink::HttpSink] waits before its first retry, doubling with every further one
+const HTTP_SINK_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// POSTs every msg as the JSON object of [crate::msg::LogMessage::render_json] to the `http://` url named by the [crate::sink::SinkDeclaration]
+/// Only msgs at or above the severity of the declaration are sent, e.g. `Error` for an alerting webhook.
+/// Msgs are POSTed on a thread of its own, at most `depth` msgs wait for it. A msg logged while the queue is full is dropped and counted,
+/// so an unreachable server never blocks the logging thread. A failed POST is retried, after the last retry the msg is dropped and counted.
+pub struct HttpSink {
+    queue: AsyncSink,
+    settings: Arc<HttpSettings>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// The retries and timeout of a [crate::sink::HttpSink], which its thread reads before every msg
+struct HttpSettings {
+    retries: AtomicU32,
+    timeout_millis: AtomicU64,
+}
+
+/// Blocks on every POST of a [crate::sink::HttpSink] and its retries, on the thread of the sink
+struct HttpPoster {
+    decl: SinkDeclaration,
+    host: String,
+    path: String,
+    settings: Arc<HttpSettings>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl HttpSink {
+    pub fn new(decl: SinkDeclaration, depth: usize) -> Result<Self> {
+        let (host, path) = http_endpoint(&decl.name, 80, "/")?;
+        decl.validate()?;
+
+        let settings = Arc::new(HttpSettings {
+            retries: AtomicU32::new(3),
+            timeout_millis: AtomicU64::new(5000),
+        });
+        let dropped = Arc::new(AtomicU64::new(0));
+        let poster = HttpPoster {
+            decl,
+            host,
+            path,
+            settings: settings.clone(),
+            dropped: dropped.clone(),
+        };
+
+        Ok(Self {
+            queue: AsyncSink::new(poster, depth)?.dropping_when_full(),
+            settings,
+            dropped,
+        })
+    }
+
+    /// Sets how often a failed POST is repeated before the msg is dropped, 3 by default
+    pub fn with_retries(self, retries: u32) -> Self {
+        self.settings.retries.store(retries, Ordering::Relaxed);
+        self
+    }
+
+    /// Sets how long connecting, sending and waiting for the answer may block the thread of the sink, 5 seconds by default
+    pub fn with_timeout(self, timeout: std::time::Duration) -> Self {
+        self.settings
+            .timeout_millis
+            .store(timeout.as_millis().max(1) as u64, Ordering::Relaxed);
+        self
+    }
+
+    /// Returns the number of msgs which were dropped, because the queue was full or every POST failed
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped() + self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl HttpPoster {
+    fn post(&self, body: &str) -> std::io::Result<()> {
+        let timeout =
+            std::time::Duration::from_millis(self.settings.timeout_millis.load(Ordering::Relaxed));
+        http_post(&self.host, &self.path, body, timeout)
+    }
+}
+
+impl Sink for HttpPoster {
+    fn log(&mut self, msg: &LogMessage) {
+        let body = msg.render_json(&self.decl);
+        let mut delay = HTTP_SINK_RETRY_DELAY;
+        for attempt in 0..=self.settings.retries.load(Ordering::Relaxed) {
+            if attempt > 0 {
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            if self.post(&body).is_ok() {
+                return;
+            }
+        }
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    decl_accessors!();
+}
+
+impl Sink for HttpSink {
+    fn name(&self) -> &str {
+        self.queue.name()
+    }
+
+    fn enabled(&self) -> bool {
+        self.queue.enabled()
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.queue.set_enabled(enabled);
+    }
+
+    fn log(&mut self, msg: &LogMessage) {
+        self.queue.log(msg);
+    }
+
+    fn severity(&self) -> LogSeverity {
+        self.queue.severity()
+    }
+
+    fn set_severity(&mut self, severity: LogSeverity) {
+        self.queue.set_severity(severity);
+    }
+
+    fn module(&self) -> &str {
+        self.queue.module()
+    }
+
+    fn tag(&self) -> Option<&str> {
+        self.queue.tag()
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        self.queue.capabilities()
+    }
+
+    fn flush_priority(&self) -> i32 {
+        self.queue.flush_priority()
+    }
+
+    /// Waits until every queued msg is POSTed or dropped
+    fn flush(&mut self) -> Result<()> {
+        self.queue.flush()
+    }
+}
+
+/// What the thread of an [crate::sink::AsyncSink] does next
+enum AsyncCommand {
+    Log(OwnedLogMessage),
+    SetSeverity(LogSeverity),
+    SetEnabled(bool),
+    Flush(std::sync::mpsc::Sender<Result<()>>),
+    Reopen(std::sync::mpsc::Sender<Result<()>>),
+}
+
+/// Passes every msg on to the inner [crate::sink::Sink] on a thread of its own, so slow sinks do not block logging
+/// At most `depth` msgs wait for the inner sink, logging blocks while the queue is full unless [crate::sink::AsyncSink::dropping_when_full].
+/// Msgs are filtered before they are queued, with the severity, module and tag the inner sink had when it was wrapped.
+/// Thread dependent patterns such as %c and %X render the values of the sink thread.
+/// Dropping the sink waits until every queued msg is logged and the inner sink is flushed.
+pub struct AsyncSink {
+    name: String,
+    enabled: bool,
+    severity: LogSeverity,
+    module: String,
+    tag: Option<String>,
+    flush_priority: i32,
+    sender: Option<std::sync::mpsc::SyncSender<AsyncCommand>>,
+    thread: Option<JoinHandle<()>>,
+    drop_when_full: bool,
+    dropped: u64,
+}
+
+impl AsyncSink {
+    pub fn new(inner: impl Sink, depth: usize) -> Result<Self> {
+        if depth == 0 {
+            return Err(Error::Config(
+                "an async sink must be allowed to queue at least one msg".to_string(),
+            ));
+        }
+
+        let name = inner.name().to_string();
+        let enabled = inner.enabled();
+        let severity = inner.severity();
+        let module = inner.module().to_string();
+        let tag = inner.tag().map(str::to_string);
+        let flush_priority = inner.flush_priority();
+
+        let (sender, receiver) = std::sync::mpsc::sync_channel(depth);
+        let mut inner = inner;
+        let thread = std::thread::Builder::new()
+            .name(format!("logtra-async-{}", name))
+            .spawn(move || {
+                for command in receiver {
+                    match command {
+                        AsyncCommand::Log(msg) => msg.with_msg(|msg| inner.log_filtered(msg)),
+                        AsyncCommand::SetSeverity(severity) => inner.set_severity(severity),
+                        AsyncCommand::SetEnabled(enabled) => inner.set_enabled(enabled),
+                        AsyncCommand::Flush(result) => {
+                            let _ = result.send(inner.flush());
+                        }
+                        AsyncCommand::Reopen(result) => {
+                            let _ = result.send(inner.reopen());
+                        }
+                    }
+                }
+                // every sender is gone, so nothing is queued anymore
+                if let Err(err) = inner.flush() {
+                    eprintln!("logtra: failed to flush {}: {}", inner.name(), err);
+                }
+            })?;
+
+        Ok(Self {
+            name,
+            enabled,
+            severity,
+            module,
+            tag,
+            flush_priority,
+            sender: Some(sender),
+            thread: Some(thread),
+            drop_when_full: false,
+            dropped: 0,
+        })
+    }
+
+    /// Drops and counts a msg logged while the queue is full instead of waiting for the inner sink
+    pub fn dropping_when_full(mut self) -> Self {
+        self.drop_when_full = true;
+        self
+    }
+
+    /// Returns the number of msgs which were dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn send(&self, command: AsyncCommand) -> Result<()> {
+        match &self.sender {
+            Some(sender) => Ok(sender.send(command)?),
+            None => Err(Error::Send("the async sink is shut down".to_string())),
+        }
+    }
+
+    /// Waits for the reply of the sink thread to the command
+    fn request(
+        &self,
+        command: impl FnOnce(std::sync::mpsc::Sender<Result<()>>) -> AsyncCommand,
+    ) -> Result<()> {
+        let (result, receiver) = std::sync::mpsc::channel();
+        self.send(command(result))?;
+        receiver
+            .recv()
+            .map_err(|_| Error::Send("the async sink thread stopped".to_string()))?
+    }
+}
+
+impl Sink for AsyncSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        let _ = self.send(AsyncCommand::SetEnabled(enabled));
+    }
+
+    fn log(&mut self, msg: &LogMessage) {
+        let command = AsyncCommand::Log(msg.to_owned());
+        match (&self.sender, self.drop_when_full) {
+            (Some(sender), true) => {
+                if let Err(std::sync::mpsc::TrySendError::Full(_)) = sender.try_send(command) {
+                    self.dropped += 1;
+                }
+            }
+            _ => {
+                let _ = self.send(command);
+            }
+        }
+    }
+
+    fn severity(&self) -> LogSeverity {
+        self.severity
+    }
+
+    fn set_severity(&mut self, severity: LogSeverity) {
+        self.severity = severity;
+        let _ = self.send(AsyncCommand::SetSeverity(severity));
+    }
+
+    fn module(&self) -> &str {
+        &self.module
+    }
+
+    fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            asynchronous: true,
+            ..Default::default()
+        }
+    }
+
+    fn flush_priority(&self) -> i32 {
+        self.flush_priority
+    }
+
+    /// Waits until every queued msg is logged, then flushes the inner sink
+    fn flush(&mut self) -> Result<()> {
+        self.request(AsyncCommand::Flush)
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        self.request(AsyncCommand::Reopen)
+    }
+}
+
+impl Drop for AsyncSink {
+    fn drop(&mut self) {
+        // closing the channel lets the thread finish the queue and stop
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Surrounds every line rendered by the inner [crate::sink::Sink] with a fixed prefix and suffix, e.g. `[worker-3] `
+/// The affixes are folded into the template of the inner sink once, the suffix is placed before a trailing newline.
+pub struct AffixSink {
+    inner: Box<dyn Sink>,
+}
+
+impl AffixSink {
+    /// Fails if the inner sink does not render a template
+    pub fn new(mut inner: impl Sink, prefix: &str, suffix: &str) -> Result<Self> {
+        let escape = |affix: &str| {
+            let mut escaped = String::with_capacity(affix.len());
+            for c in affix.chars() {
+                if c == '%' || c == '\\' {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        };
+
+        let decl = inner.declaration_mut().ok_or_else(|| {
+            Error::Config("an affix sink can only wrap a sink rendering a template".to_string())
+        })?;
+        let (body, newline) = match decl.template.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (decl.template.as_str(), ""),
+        };
+        decl.template = format!("{}{}{}{}", escape(prefix), body, escape(suffix), newline);
+        decl.validate()?;
+
+        Ok(Self {
+            inner: Box::new(inner),
+        })
+    }
+}
+
+impl Sink for AffixSink {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn enabled(&self) -> bool {
+        self.inner.enabled()
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.inner.set_enabled(enabled);
+    }
+
+    fn log(&mut self, msg: &LogMessage) {
+        self.inner.log(msg);
+    }
+
+    fn severity(&self) -> LogSeverity {
+        self.inner.severity()
+    }
+
+    fn set_severity(&mut self, severity: LogSeverity) {
+        self.inner.set_severity(severity);
+    }
+
+    fn module(&self) -> &str {
+        self.inner.module()
+    }
+
+    fn tag(&self) -> Option<&str> {
+        self.inner.tag()
+    }
+
+    fn sample_rate(&self, severity: LogSeverity) -> u8 {
+        self.inner.sample_rate(severity)
+    }
+
+    fn sampling_seed(&self) -> Option<u64> {
+        self.inner.sampling_seed()
+    }
+
+    fn flush_priority(&self) -> i32 {
+        self.inner.flush_priority()
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        self.inner.reopen()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn declaration_mut(&mut self) -> Option<&mut SinkDeclaration> {
+        self.inner.declaration_mut()
+    }
+}
+
+/// The first msg of a burst and how often it occurred, kept by the [crate::sink::CoalesceSink]
+struct Burst {
+    module: String,
+    file: String,
+    line: u32,
+    msg: String,
+    severity: LogSeverity,
+    color: Color,
+    style: Style,
+    tag: Option<String>,
+    fields: Vec<(String, String)>,
+    uptime: std::time::Duration,
+    first: std::time::SystemTime,
+    last: std::time::SystemTime,
+    count: u64,
+}
+
+/// Collapses bursts of msgs with the same key into a single summary per window before passing it on to the inner [crate::sink::Sink]
+/// The window of a key starts at its first msg. Once a later msg is at least a window younger, every expired burst is summarized as
+/// "<first msg> occurred <n> times between <first timestamp> and <last timestamp>", or passed on unchanged if it occurred only once.
+/// The remaining bursts are summarized on [crate::sink::CoalesceSink::flush] and on drop.
+pub struct CoalesceSink {
+    inner: Box<dyn Sink>,
+    window: std::time::Duration,
+    key_fn: Box<dyn Fn(&LogMessage) -> u64 + Send>,
+    bursts: HashMap<u64, Burst>,
+}
+
+impl CoalesceSink {
+    pub fn new(
+        inner: impl Sink,
+        window: std::time::Duration,
+        key_fn: impl Fn(&LogMessage) -> u64 + Send + 'static,
+    ) -> Self {
+        Self {
+            inner: Box::new(inner),
+            window,
+            key_fn: Box::new(key_fn),
+            bursts: HashMap::new(),
+        }
+    }
+
+    fn summarize(&mut self, expired: impl Fn(&Burst) -> bool) {
+        let expired: Vec<u64> = self
+            .bursts
+            .iter()
+            .filter(|(_, burst)| expired(burst))
+            .map(|(key, _)| *key)
+            .collect();
+        let mut summarized: Vec<Burst> = expired
+            .iter()
+            .filter_map(|key| self.bursts.remove(key))
+            .collect();
+        summarized.sort_by_key(|burst| burst.first);
+
+        for burst in summarized {
+            let summary = match burst.count {
+                1 => burst.msg,
+                count => format!(
+                    "{} occurred {} times between {} and {}",
+                    burst.msg,
+                    count,
+                    chrono::DateTime::<chrono::Utc>::from(burst.first).to_rfc3339(),
+                    chrono::DateTime::<chrono::Utc>::from(burst.last).to_rfc3339()
+                ),
+            };
+            let fields: Vec<(&str, &str)> = burst
+                .fields
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            self.inner.log(&LogMessage {
+                time: burst.last,
+                module: &burst.module,
+                file: &burst.file,
+                line: burst.line,
+                msg: &summary,
+                severity: burst.severity,
+                color: burst.color,
+                style: burst.style,
+                tag: burst.tag.as_deref(),
+                fields: &fields,
+                uptime: burst.uptime,
+            });
+        }
+    }
+}
+
+impl Sink for CoalesceSink {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn enabled(&self) -> bool {
+        self.inner.enabled()
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.inner.set_enabled(enabled);
+    }
+
+    fn log(&mut self, msg: &LogMessage) {
+        let window = self.window;
+        self.summarize(|burst| {
+            msg.time
+                .duration_since(burst.first)
+                .is_ok_and(|elapsed| elapsed >= window)
+        });
+
+        let key = (self.key_fn)(msg);
+        match self.bursts.get_mut(&key) {
+            Some(burst) => {
+                burst.count += 1;
+                burst.last = burst.last.max(msg.time);
+            }
+            None => {
+                self.bursts.insert(
+                    key,
+                    Burst {
+                        module: msg.module.to_string(),
+                        file: msg.file.to_string(),
+                        line: msg.line,
+                        msg: msg.msg.to_string(),
+                        severity: msg.severity,
+                        color: msg.color,
+                        style: msg.style,
+                        tag: msg.tag.map(str::to_string),
+                        fields: msg
+                            .fields
+                            .iter()
+                            .map(|(key, value)| (key.to_string(), value.to_string()))
+                            .collect(),
+                        uptime: msg.uptime,
+                        first: msg.time,
+                        last: msg.time,
+                        count: 1,
+                    },
+                );
+            }
+        }
+    }
+
+    fn severity(&self) -> LogSeverity {
+        self.inner.severity()
+    }
+
+    fn set_severity(&mut self, severity: LogSeverity) {
+        self.inner.set_severity(severity);
+    }
+
+    fn module(&self) -> &str {
+        self.inner.module()
+    }
+
+    fn tag(&self) -> Option<&str> {
+        self.inner.tag()
+    }
+
+    fn sample_rate(&self, severity: LogSeverity) -> u8 {
+        self.inner.sample_rate(severity)
+    }
+
+    fn sampling_seed(&self) -> Option<u64> {
+        self.inner.sampling_seed()
+    }
+
+    fn flush_priority(&self) -> i32 {
+        self.inner.flush_priority()
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            flushable: true,
+            ..self.inner.capabilities()
+        }
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        self.inner.reopen()
+    }
+
+    /// Summarizes every pending burst and flushes the inner sink
+    fn flush(&mut self) -> Result<()> {
+        self.summarize(|_| true);
+        self.inner.flush()
+    }
+
+    fn declaration_mut(&mut self) -> Option<&mut SinkDeclaration> {
+        self.inner.declaration_mut()
+    }
+}
+
+impl Drop for CoalesceSink {
+    fn drop(&mut self) {
+        self.summarize(|_| true);
+    }
+}
+
+/// Masks every match of a pattern in the msg (%m) and the values of its fields (%k) before passing it on to the inner [crate::sink::Sink]
+#[cfg(feature = "regex")]
+pub struct RegexRedactSink {
+    inner: Box<dyn Sink>,
+    pattern: regex::Regex,
+    mask: String,
+}
+
+#[cfg(feature = "regex")]
+impl RegexRedactSink {
+    /// Compiles the pattern once, failing if it is not a valid regex
+    pub fn new(inner: impl Sink, pattern: &str, mask: &str) -> Result<Self> {
+        Ok(Self {
+            inner: Box::new(inner),
+            pattern: regex::Regex::new(pattern).map_err(|err| Error::Config(err.to_string()))?,
+            mask: mask.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Sink for RegexRedactSink {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn enabled(&self) -> bool {
+        self.inner.enabled()
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.inner.set_enabled(enabled);
+    }
+
+    fn log(&mut self, msg: &LogMessage) {
+        let redacted = self
+            .pattern
+            .replace_all(msg.msg, regex::NoExpand(&self.mask));
+        let redacted_values: Vec<_> = msg
+            .fields
+            .iter()
+            .map(|(_, value)| self.pattern.replace_all(value, regex::NoExpand(&self.mask)))
+            .collect();
+        let fields: Vec<(&str, &str)> = msg
+            .fields
+            .iter()
+            .zip(redacted_values.iter())
+            .map(|((key, _), value)| (*key, value.as_ref()))
+            .collect();
+        self.inner.log(&LogMessage {
+            msg: &redacted,
+            fields: &fields,
+            ..*msg
+        });
+    }
+
+    fn severity(&self) -> LogSeverity {
+        self.inner.severity()
+    }
+
+    fn set_severity(&mut self, severity: LogSeverity) {
+        self.inner.set_severity(severity);
+    }
+
+    fn module(&self) -> &str {
+        self.inner.module()
+    }
+
+    fn tag(&self) -> Option<&str> {
+        self.inner.tag()
+    }
+
+    fn sample_rate(&self, severity: LogSeverity) -> u8 {
+        self.inner.sample_rate(severity)
+    }
+
+    fn sampling_seed(&self) -> Option<u64> {
+        self.inner.sampling_seed()
+    }
+
+    fn flush_priority(&self) -> i32 {
+        self.inner.flush_priority()
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        self.inner.reopen()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn declaration_mut(&mut self) -> Option<&mut SinkDeclaration> {
+        self.inner.declaration_mut()
+    }
+}
+
+/// Compresses every msg into the gzip file named by the [crate::sink::SinkDeclaration] while writing
+/// The compressor is synced every 1000 msgs and on the flush severity, so everything up to then can be decompressed even if the process dies.
+/// Msgs after the last sync only reach the file once the sink is dropped, which is why tailing the file lags behind.
+/// An existing file is appended to as another gzip member, which a multi-member decoder such as `zcat` reads in order.
+/// Msgs which can not be written are dropped and counted.
+#[cfg(feature = "flate2")]
+pub struct GzipFileSink {
+    decl: SinkDeclaration,
+    encoder: flate2::write::GzEncoder<File>,
+    unsynced: usize,
+    dropped: u64,
+}
+
+#[cfg(feature = "flate2")]
+impl GzipFileSink {
+    pub fn new(decl: SinkDeclaration) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of a gzip file sink must be a path".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&decl.name)?;
+        Ok(Self {
+            decl,
+            encoder: flate2::write::GzEncoder::new(file, flate2::Compression::default()),
+            unsynced: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Returns the number of msgs which could not be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(feature = "flate2")]
+impl Sink for GzipFileSink {
+    fn log(&mut self, msg: &LogMessage) {
+        if self
+            .encoder
+            .write_all(msg.render(&self.decl).as_bytes())
+            .is_err()
+        {
+            self.dropped += 1;
+            return;
+        }
+
+        self.unsynced += 1;
+        if self.unsynced >= FILE_SINK_BUFFER_SIZE || self.decl.flushes_on(msg.severity) {
+            let _ = self.flush();
+        }
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            flushable: true,
+            ..Default::default()
+        }
+    }
+
+    /// Syncs the compressor, so everything written so far can be decompressed
+    fn flush(&mut self) -> Result<()> {
+        self.unsynced = 0;
+        Ok(self.encoder.flush()?)
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.decl.name)?;
+        let previous = std::mem::replace(
+            &mut self.encoder,
+            flate2::write::GzEncoder::new(file, flate2::Compression::default()),
+        );
+        previous.finish()?;
+        Ok(())
+    }
+
+    decl_accessors!();
+}
+
+#[cfg(feature = "flate2")]
+impl Drop for GzipFileSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.encoder.try_finish() {
+            eprintln!("logtra: failed to finish {}: {}", self.decl.name, err);
+        }
+    }
+}
+
+/// The type a msg is reported as by the [crate::sink::EventLogSink]
+#[cfg(feature = "eventlog")]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum EventType {
+    Error = 0x0001,
+    Warning = 0x0002,
+    Information = 0x0004,
+}
+
+#[cfg(feature = "eventlog")]
+impl EventType {
+    pub fn from_severity(severity: LogSeverity) -> Self {
+        match severity {
+            LogSeverity::Verbose | LogSeverity::Trace | LogSeverity::Debug | LogSeverity::Info => {
+                EventType::Information
+            }
+            LogSeverity::Warn => EventType::Warning,
+            LogSeverity::Error | LogSeverity::Fatal => EventType::Error,
+        }
+    }
+}
+
+/// Renders the msg as the nul terminated UTF-16 string expected by the event log
+#[cfg(feature = "eventlog")]
+#[cfg_attr(not(windows), allow(dead_code))]
+fn event_message(decl: &SinkDeclaration, msg: &LogMessage) -> Vec<u16> {
+    msg.render(decl)
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+#[cfg(all(windows, feature = "eventlog"))]
+mod eventlog {
+    use std::ffi::c_void;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        pub fn RegisterEventSourceW(server: *const u16, source: *const u16) -> *mut c_void;
+        pub fn DeregisterEventSource(handle: *mut c_void) -> i32;
+        pub fn ReportEventW(
+            handle: *mut c_void,
+            event_type: u16,
+            category: u16,
+            event_id: u32,
+            user_sid: *mut c_void,
+            num_strings: u16,
+            data_size: u32,
+            strings: *const *const u16,
+            raw_data: *mut c_void,
+        ) -> i32;
+    }
+}
+
+/// Reports every msg to the Windows event log under the source named by the [crate::sink::SinkDeclaration]
+/// Opening a source is idempotent and works for unregistered sources, whose events Event Viewer shows without a description.
+/// Registering the source with a message file requires administrator rights and is left to the installer.
+/// Msgs which can not be reported are dropped and counted.
+#[cfg(all(windows, feature = "eventlog"))]
+pub struct EventLogSink {
+    decl: SinkDeclaration,
+    /// The event source handle, stored as an address so the sink is Send
+    handle: usize,
+    dropped: u64,
+}
+
+#[cfg(all(windows, feature = "eventlog"))]
+impl EventLogSink {
+    pub fn new(decl: SinkDeclaration) -> Result<Self> {
+        if decl.name.is_empty() {
+            return Err(Error::Config(
+                "the name of an event log sink must be an event source".to_string(),
+            ));
+        }
+        decl.validate()?;
+
+        let source: Vec<u16> = decl.name.encode_utf16().chain(std::iter::once(0)).collect();
+        let handle = unsafe { eventlog::RegisterEventSourceW(std::ptr::null(), source.as_ptr()) };
+        if handle.is_null() {
+            return Err(Error::Io(std::io::Error::last_os_error()));
+        }
+
+        Ok(Self {
+            decl,
+            handle: handle as usize,
+            dropped: 0,
+        })
+    }
+
+    /// Returns the number of msgs which could not be reported
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(all(windows, feature = "eventlog"))]
+impl Sink for EventLogSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let message = event_message(&self.decl, msg);
+        let strings = [message.as_ptr()];
+        let reported = unsafe {
+            eventlog::ReportEventW(
+                self.handle as *mut std::ffi::c_void,
+                EventType::from_severity(msg.severity) as u16,
+                0,
+                0,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null_mut(),
+            )
+        };
+        if reported == 0 {
+            self.dropped += 1;
+        }
+    }
+
+    decl_accessors!();
+}
+
+#[cfg(all(windows, feature = "eventlog"))]
+impl Drop for EventLogSink {
+    fn drop(&mut self) {
+        unsafe {
+            eventlog::DeregisterEventSource(self.handle as *mut std::ffi::c_void);
+        }
+    }
+}
+
+/// Maps a [crate::msg::LogSeverity] to the OpenTelemetry SeverityNumber
+#[cfg(feature = "otlp")]
+pub fn otlp_severity_number(severity: LogSeverity) -> u8 {
+    match severity {
+        LogSeverity::Verbose => 1,
+        LogSeverity::Trace => 2,
+        LogSeverity::Debug => 5,
+        LogSeverity::Info => 9,
+        LogSeverity::Warn => 13,
+        LogSeverity::Error => 17,
+        LogSeverity::Fatal => 21,
+    }
+}
+
+/// Exports msgs as OpenTelemetry log records to the OTLP/HTTP JSON endpoint named by the [crate::sink::SinkDeclaration], e.g. "http://localhost:4318/v1/logs"
+/// The port defaults to 4318 and the path to `/v1/logs`, as in the OTLP/HTTP specification.
+/// The rendered msg becomes the body, module, file, line, tag and the structured fields become attributes.
+/// Records are exported in batches, or immediately on the flush severity. Records of a failed export are dropped and counted.
+#[cfg(feature = "otlp")]
+pub struct OtlpSink {
+    decl: SinkDeclaration,
+    host: String,
+    path: String,
+    batch_size: usize,
+    batch: Vec<String>,
+    dropped: u64,
+}
+
+#[cfg(feature = "otlp")]
+impl OtlpSink {
+    pub fn new(decl: SinkDeclaration, batch_size: usize) -> Result<Self> {
+        let (host, path) = http_endpoint(&decl.name, 4318, "/v1/logs")?;
+        decl.validate()?;
+
+        Ok(Self {
+            host,
+            path,
+            batch_size: batch_size.max(1),
+            batch: Vec::with_capacity(batch_size.max(1)),
+            decl,
+            dropped: 0,
+        })
+    }
+
+    /// Returns the number of records which could not be exported
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn record(&self, msg: &LogMessage) -> String {
+        let time = msg
+            .time
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        let mut record = format!(
+            "{{\"timeUnixNano\":\"{}\",\"severityNumber\":{},\"severityText\":\"{}\",\"body\":{{\"stringValue\":\"",
+            time,
+            otlp_severity_number(msg.severity),
+            msg.severity.as_str().trim_end()
+        );
+        crate::msg::json_escape(&mut record, &msg.render(&self.decl));
+        record.push_str(
+            "\"},\"attributes\":[{\"key\":\"code.namespace\",\"value\":{\"stringValue\":\"",
+        );
+        crate::msg::json_escape(&mut record, msg.module);
+        if !self.decl.strip_location {
+            record.push_str("\"}},{\"key\":\"code.filepath\",\"value\":{\"stringValue\":\"");
+            crate::msg::json_escape(&mut record, msg.file);
+            record.push_str(&format!(
+                "\"}}}},{{\"key\":\"code.lineno\",\"value\":{{\"intValue\":\"{}",
+                msg.line
+            ));
+        }
+        if let Some(tag) = msg.tag {
+            record.push_str("\"}},{\"key\":\"logtra.tag\",\"value\":{\"stringValue\":\"");
+            crate::msg::json_escape(&mut record, tag);
+        }
+        for (key, value) in msg.fields {
+            record.push_str("\"}},{\"key\":\"");
+            crate::msg::json_escape(&mut record, key);
+            record.push_str("\",\"value\":{\"stringValue\":\"");
+            crate::msg::json_escape(&mut record, value);
+        }
+        record.push_str("\"}}]}");
+        record
+    }
+
+    fn export(&self, records: &[String]) -> Result<()> {
+        let body = format!(
+            "{{\"resourceLogs\":[{{\"resource\":{{}},\"scopeLogs\":[{{\"scope\":{{\"name\":\"logtra\"}},\"logRecords\":[{}]}}]}}]}}",
+            records.join(",")
+        );
+        Ok(http_post(
+            &self.host,
+            &self.path,
+            &body,
+            std::time::Duration::from_secs(5),
+        )?)
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl Sink for OtlpSink {
+    fn log(&mut self, msg: &LogMessage) {
+        let record = self.record(msg);
+        self.batch.push(record);
+        if self.batch.len() >= self.batch_size || self.decl.flushes_on(msg.severity) {
+            let _ = self.flush();
+        }
+    }
+
+    /// Exports the pending records
+    fn flush(&mut self) -> Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+
+        let records = std::mem::take(&mut self.batch);
+        let result = self.export(&records);
+        if result.is_err() {
+            self.dropped += records.len() as u64;
+        }
+        result
+    }
+
+    fn capabilities(&self) -> SinkCapabilities {
+        SinkCapabilities {
+            structured_fields: true,
+            flushable: true,
+            ..Default::default()
+        }
+    }
+
+    decl_accessors!();
+}
+
+#[cfg(feature = "otlp")]
+impl Drop for OtlpSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        fs::{read_to_string, remove_file},
+        path::Path,
+        time::Duration,
+    };
+
+    use chrono::{DateTime, Utc};
+
+    use crate::{
+        msg::{Color, LogMessage, LogSeverity, Style},
+        sink::{ConsoleSink, DebugSink, Sink, SinkCapabilities, SinkDeclaration, VoidSink},
+        Error,
+    };
+
+    use super::FileSink;
+
+    /// A msg logged at the epoch from this file, tests override other fields with struct update syntax
+    fn message(msg: &str, severity: LogSeverity) -> LogMessage<'_> {
+        LogMessage {
+            time: DateTime::<Utc>::default().into(),
+            module: "logtra",
+            file: file!(),
+            line: line!(),
+            msg,
+            severity,
+            color: Color::Red,
+            style: Style::NONE,
+            tag: None,
+            fields: &[],
+            uptime: Duration::ZERO,
+        }
+    }
+
+    struct CountingSink {
+        decl: SinkDeclaration,
+        count: usize,
+    }
+
+    impl Sink for CountingSink {
+        fn log(&mut self, _msg: &LogMessage) {
+            self.count += 1;
+        }
+
+        decl_accessors!();
+    }
+
+    #[test]
+    fn console_sink_works() {
+        let decl = SinkDeclaration {
+            name: "Default".to_string(),
+            severity: LogSeverity::Info,
+            module: "".to_string(),
+            template: "[%t][%c%s%c][%f:%l]: %m\n".to_string(),
+            ..Default::default()
+        };
+        let msg = message("Hello world!", LogSeverity::Info);
+
+        let mut sink = ConsoleSink::new(decl);
+        sink.log(&msg);
+    }
+
+    #[test]
+    fn declared_colors_work() {
+        let decl = SinkDeclaration {
+            template: "[%[%i%]]: %m".to_string(),
+            colors: HashMap::from([(LogSeverity::Warn, Color::Yellow)]),
+            ..Default::default()
+        };
+        let mut msg = LogMessage {
+            color: Color::Orange,
+            ..message("themed", LogSeverity::Warn)
+        };
+
+        crate::msg::override_no_color(Some(false));
+        let warn = msg.render(&decl);
+        // severities without a color keep the color of the msg
+        msg.severity = LogSeverity::Error;
+        msg.color = Color::Red;
+        let error = msg.render(&decl);
+        // another sink renders the same msg with its own theme
+        let light = msg.render(&SinkDeclaration {
+            colors: HashMap::from([(LogSeverity::Error, Color::Rgb(255, 0, 0))]),
+            ..decl.clone()
+        });
+        crate::msg::override_no_color(None);
+
+        assert_eq!("[\x1b[33mWarn \x1b[0m]: themed", warn);
+        assert_eq!("[\x1b[31mError\x1b[0m]: themed", error);
+        assert_eq!("[\x1b[38;2;255;0;0mError\x1b[0m]: themed", light);
+    }
+
+    #[test]
+    fn console_sink_colors_can_be_forced() {
+        let decl = SinkDeclaration {
+            template: "[%[%i%]]: %m".to_string(),
+            ..Default::default()
+        };
+        let msg = message("piped", LogSeverity::Info);
+
+        crate::msg::override_no_color(Some(false));
+        let mut sink = ConsoleSink::new(decl.clone()).with_colors(true);
+        assert_eq!("[\x1b[31mInfo \x1b[0m]: piped", sink.render(&msg));
+        let mut sink = ConsoleSink::new(decl.clone()).with_colors(false);
+        assert_eq!("[Info ]: piped", sink.render(&msg));
+        crate::msg::override_no_color(None);
+        // the declaration is left as it was
+        assert_eq!(Some(decl), sink.config().map(|config| config.decl));
+    }
+
+    #[test]
+    fn stderr_sink_works() {
+        use super::{SinkConfigEntry, SinkKind, StderrSink};
+
+        let decl = SinkDeclaration {
+            name: "stderr".to_string(),
+            severity: LogSeverity::Error,
+            template: "[%i][%f:%l]: %m\n".to_string(),
+            ..Default::default()
+        };
+        let mut msg = LogMessage {
+            file: "main.rs",
+            line: 7,
+            ..message("Hello stderr!", LogSeverity::Error)
+        };
+
+        let mut sink: Box<dyn Sink> = Box::new(StderrSink::new(decl.clone()));
+        assert_eq!(LogSeverity::Error, sink.severity());
+        assert_eq!("[Error][main.rs:7]: Hello stderr!\n", msg.render(&decl));
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Warn;
+        sink.log_filtered(&msg);
+
+        assert_eq!(
+            Some(SinkConfigEntry {
+                kind: SinkKind::Stderr,
+                decl
+            }),
+            sink.config()
+        );
+    }
+
+    #[test]
+    fn split_console_sink_works() {
+        use super::SplitConsoleSink;
+
+        let sink = SplitConsoleSink::new(SinkDeclaration::default());
+        let routed: Vec<bool> = [
+            LogSeverity::Verbose,
+            LogSeverity::Trace,
+            LogSeverity::Debug,
+            LogSeverity::Info,
+            LogSeverity::Warn,
+            LogSeverity::Error,
+            LogSeverity::Fatal,
+        ]
+        .into_iter()
+        .map(|severity| sink.to_stderr(severity))
+        .collect();
+        assert_eq!(vec![false, false, false, false, true, true, true], routed);
+
+        let sink = sink.with_threshold(LogSeverity::Error);
+        assert!(!sink.to_stderr(LogSeverity::Warn));
+        assert!(sink.to_stderr(LogSeverity::Error));
+    }
+
+    #[test]
+    fn buffered_console_sink_works() {
+        use std::{
+            io::Write,
+            sync::{
+                mpsc::{channel, Receiver, Sender},
+                Arc, Mutex,
+            },
+        };
+
+        use super::BufferedConsoleSink;
+
+        /// Blocks on its first write until released, like a paused terminal
+        struct StalledWriter {
+            output: Arc<Mutex<Vec<u8>>>,
+            stalled: Sender<()>,
+            release: Receiver<()>,
+        }
+
+        impl Write for StalledWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.stalled.send(()).is_ok() {
+                    let _ = self.release.recv();
+                }
+                self.output.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let (stalled, stalled_receiver) = channel();
+        let (release, release_receiver) = channel();
+        let writer = StalledWriter {
+            output: output.clone(),
+            stalled,
+            release: release_receiver,
+        };
+        let decl = SinkDeclaration {
+            template: "%m\n".to_string(),
+            ..Default::default()
+        };
+        let mut msg = message("line 0", LogSeverity::Info);
+
+        let mut sink = BufferedConsoleSink::with_writer(decl, 2, writer).unwrap();
+        sink.log_filtered(&msg);
+        stalled_receiver.recv().unwrap();
+        // the first write stalls from now on, only the newest two lines are kept
+        let lines: Vec<String> = (1..10).map(|i| format!("line {}", i)).collect();
+        for line in lines.iter() {
+            msg.msg = line;
+            sink.log_filtered(&msg);
+        }
+        // stops stalling every further write
+        drop(stalled_receiver);
+        release.send(()).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            "line 0\nlogtra: dropped 7 console lines\nline 8\nline 9\n",
+            String::from_utf8(output.lock().unwrap().clone()).unwrap()
+        );
+        assert!(BufferedConsoleSink::new(SinkDeclaration::default(), 0).is_err());
+    }
+
+    #[test]
+    fn debug_sink_works() {
+        let msg = LogMessage {
+            tag: Some("auth"),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+
+        let dump = DebugSink::dump(&msg);
+        for field in [
+            "time:",
+            "module:",
+            "file:",
+            "line:",
+            "msg:",
+            "severity:",
+            "color:",
+            "tag:",
+            "uptime:",
+        ] {
+            assert!(dump.contains(field), "{} is missing in {}", field, dump);
+        }
+        assert!(dump.contains("\"Hello world!\""));
+
+        let mut sink = DebugSink::new(SinkDeclaration::default());
+        sink.log_filtered(&msg);
+    }
+
+    #[test]
     fn file_sink_works() {
         let decl = SinkDeclaration {
-            name: "example.log".to_string(),
-            severity: LogSeverity::Info,
-            module: "".to_string(),
-            template: "[%t][%s][%f:%l]: %m\n".to_string(),
+            name: "example.log".to_string(),
+            severity: LogSeverity::Info,
+            module: "".to_string(),
+            template: "[%t][%s][%f:%l]: %m\n".to_string(),
+            ..Default::default()
+        };
+        let msg = message("Hello world!", LogSeverity::Info);
+
+        {
+            let mut sink = FileSink::new(decl).unwrap();
+            sink.log(&msg);
+            sink.log(&msg);
+            sink.log(&msg);
+            sink.log(&msg);
+        }
+
+        assert!(remove_file(Path::new("example.log")).is_ok());
+    }
+
+    #[test]
+    fn tag_filtering_works() {
+        let mut sink = CountingSink {
+            decl: SinkDeclaration {
+                tag: Some("auth".to_string()),
+                ..Default::default()
+            },
+            count: 0,
+        };
+        let mut msg = LogMessage {
+            tag: Some("auth"),
+            ..message("Hello world!", LogSeverity::Info)
+        };
+
+        sink.log_filtered(&msg);
+        msg.tag = Some("billing");
+        sink.log_filtered(&msg);
+        msg.tag = None;
+        sink.log_filtered(&msg);
+        assert_eq!(1, sink.count);
+
+        sink.decl.tag = None;
+        sink.log_filtered(&msg);
+        assert_eq!(2, sink.count);
+    }
+
+    #[test]
+    fn verbose_is_below_trace() {
+        let mut sink = CountingSink {
+            decl: SinkDeclaration {
+                severity: LogSeverity::Trace,
+                ..Default::default()
+            },
+            count: 0,
+        };
+        let mut msg = LogMessage {
+            color: Color::DimGrey,
+            ..message("Hello world!", LogSeverity::Verbose)
+        };
+
+        assert!(LogSeverity::Verbose < LogSeverity::Trace);
+        assert_eq!("Verb ", LogSeverity::Verbose.to_string());
+        sink.log_filtered(&msg);
+        assert_eq!(0, sink.count);
+
+        msg.severity = LogSeverity::Trace;
+        sink.log_filtered(&msg);
+        assert_eq!(1, sink.count);
+
+        msg.severity = LogSeverity::Verbose;
+        sink.decl.severity = LogSeverity::Verbose;
+        sink.log_filtered(&msg);
+        assert_eq!(2, sink.count);
+    }
+
+    #[test]
+    fn file_sink_flushes_on_severity() {
+        let decl = SinkDeclaration {
+            name: "flush_severity.log".to_string(),
+            template: "%m\n".to_string(),
+            flush_severity: Some(LogSeverity::Error),
+            ..Default::default()
+        };
+        let mut msg = message("buffered", LogSeverity::Info);
+
+        {
+            let mut sink = FileSink::new(decl).unwrap();
+            sink.log_filtered(&msg);
+            sink.log_filtered(&msg);
+            sink.log_filtered(&msg);
+            assert!(!Path::new("flush_severity.log").exists());
+
+            msg.msg = "failed";
+            msg.severity = LogSeverity::Error;
+            sink.log_filtered(&msg);
+            assert_eq!(
+                "buffered\nbuffered\nbuffered\nfailed\n",
+                read_to_string("flush_severity.log").unwrap()
+            );
+        }
+
+        assert!(remove_file(Path::new("flush_severity.log")).is_ok());
+    }
+
+    #[test]
+    fn sink_severity_rendering_works() {
+        use super::MemorySink;
+
+        let mut info = MemorySink::new(SinkDeclaration {
+            template: "%i >= %I".to_string(),
+            ..Default::default()
+        });
+        let mut trace = MemorySink::new(SinkDeclaration {
+            severity: LogSeverity::Trace,
+            template: "%i >= %I".to_string(),
+            ..Default::default()
+        });
+        let msg = message("Hello world!", LogSeverity::Warn);
+
+        info.log_filtered(&msg);
+        trace.log_filtered(&msg);
+        assert_eq!(vec!["Warn  >= Info "], *info.lines().lock().unwrap());
+        assert_eq!(vec!["Warn  >= Trace"], *trace.lines().lock().unwrap());
+        assert_eq!("Warn  >= ", msg.parse("%i >= %I"));
+    }
+
+    #[test]
+    fn sharded_file_sink_works() {
+        use super::ShardedFileSink;
+
+        let directory = std::env::temp_dir().join(format!("logtra-{}-shards", std::process::id()));
+        let decl = SinkDeclaration {
+            name: directory.to_str().unwrap().to_string(),
+            template: "%m\n".to_string(),
+            ..Default::default()
+        };
+        let mut msg = LogMessage {
+            module: "auth::login",
+            ..message("logged in", LogSeverity::Info)
+        };
+
+        // a single open file forces every shard switch to close and reopen a file
+        let mut sink = ShardedFileSink::new(decl, 1).unwrap();
+        sink.log_filtered(&msg);
+        msg.module = "billing::invoice";
+        msg.msg = "invoice sent";
+        sink.log_filtered(&msg);
+        msg.module = "auth";
+        msg.msg = "logged out";
+        sink.log_filtered(&msg);
+        drop(sink);
+
+        assert_eq!(
+            "logged in\nlogged out\n",
+            read_to_string(directory.join("auth.log")).unwrap()
+        );
+        assert_eq!(
+            "invoice sent\n",
+            read_to_string(directory.join("billing.log")).unwrap()
+        );
+        assert!(std::fs::remove_dir_all(&directory).is_ok());
+    }
+
+    #[test]
+    fn sink_capabilities_work() {
+        let console = ConsoleSink::new(SinkDeclaration::default());
+        assert!(console.capabilities().color);
+        assert!(!console.capabilities().flushable);
+
+        {
+            let file = FileSink::new(SinkDeclaration {
+                name: "capabilities.log".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+            assert!(file.capabilities().flushable);
+            assert!(!file.capabilities().color);
+        }
+        assert!(remove_file(Path::new("capabilities.log")).is_ok());
+
+        let void = VoidSink::new(SinkDeclaration::default());
+        assert_eq!(SinkCapabilities::default(), void.capabilities());
+    }
+
+    #[test]
+    fn sampling_works() {
+        let mut sink = CountingSink {
+            decl: SinkDeclaration {
+                severity: LogSeverity::Trace,
+                sampling: HashMap::from([(LogSeverity::Debug, 10)]),
+                ..Default::default()
+            },
+            count: 0,
+        };
+        let mut msg = message("Hello world!", LogSeverity::Debug);
+
+        for _ in 0..10000 {
+            sink.log_filtered(&msg);
+        }
+        assert!((800..1200).contains(&sink.count), "{}", sink.count);
+
+        sink.count = 0;
+        msg.severity = LogSeverity::Warn;
+        for _ in 0..10000 {
+            sink.log_filtered(&msg);
+        }
+        assert_eq!(10000, sink.count);
+    }
+
+    #[test]
+    fn seeded_sampling_works() {
+        let decl = |seed| SinkDeclaration {
+            severity: LogSeverity::Trace,
+            sampling: HashMap::from([(LogSeverity::Debug, 10)]),
+            sampling_seed: Some(seed),
+            ..Default::default()
+        };
+        let decisions = |decl: SinkDeclaration| {
+            let mut sink = CountingSink { decl, count: 0 };
+            (0..10000u64)
+                .map(|i| {
+                    let msg = LogMessage {
+                        time: (DateTime::<Utc>::default()
+                            + chrono::Duration::microseconds(i as i64))
+                        .into(),
+                        ..message("Hello world!", LogSeverity::Debug)
+                    };
+                    let before = sink.count;
+                    sink.log_filtered(&msg);
+                    sink.count > before
+                })
+                .collect::<Vec<bool>>()
+        };
+
+        let first = decisions(decl(42));
+        let kept = first.iter().filter(|kept| **kept).count();
+        assert!((800..1200).contains(&kept), "{}", kept);
+        assert_eq!(first, decisions(decl(42)));
+        assert_ne!(first, decisions(decl(7)));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_redact_sink_works() {
+        use super::{MemorySink, RegexRedactSink};
+
+        let memory = MemorySink::new(SinkDeclaration {
+            template: "%m".to_string(),
+            ..Default::default()
+        });
+        let lines = memory.lines();
+        let emails = RegexRedactSink::new(memory, r"[\w.+-]+@[\w-]+\.[\w.]+", "***").unwrap();
+        let mut sink =
+            RegexRedactSink::new(emails, r"token=[0-9a-f]{8}", "token=********").unwrap();
+
+        let mut msg = message("sent mail to jane.doe@example.com", LogSeverity::Info);
+        sink.log_filtered(&msg);
+        msg.msg = "authenticated with token=deadbeef";
+        sink.log_filtered(&msg);
+
+        assert_eq!(
+            vec!["sent mail to ***", "authenticated with token=********"],
+            *lines.lock().unwrap()
+        );
+        assert!(matches!(
+            RegexRedactSink::new(VoidSink::new(SinkDeclaration::default()), "(", ""),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn file_sink_errors_work() {
+        assert!(matches!(
+            FileSink::new(SinkDeclaration::default()),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            FileSink::new(SinkDeclaration {
+                name: "invalid.log".to_string(),
+                template: "[%q]: %m".to_string(),
+                ..Default::default()
+            }),
+            Err(Error::TemplateParse(_))
+        ));
+
+        let mut sink = FileSink::new(SinkDeclaration {
+            name: "missing/directory/file.log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(matches!(sink.flush(), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn file_sink_flush_errors_are_returned() {
+        let mut sink = FileSink::new(SinkDeclaration {
+            name: "missing/directory/file.log".to_string(),
+            flush_severity: Some(LogSeverity::Error),
+            ..Default::default()
+        })
+        .unwrap();
+        let msg = message("never written", LogSeverity::Error);
+
+        assert!(sink.last_error().is_none());
+        sink.log_filtered(&msg);
+        assert!(matches!(sink.last_error(), Some(Error::Io(_))));
+        assert!(matches!(sink.flush(), Err(Error::Io(_))));
+
+        // dropping the sink reports the retained msg on stderr instead of panicking
+        let dropped = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(sink)));
+        assert!(dropped.is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_sink_works() {
+        use std::{
+            io::{BufRead, BufReader},
+            os::unix::net::{UnixDatagram, UnixListener},
+            thread,
+        };
+
+        use super::{Framing, UnixSocketKind, UnixSocketSink};
+
+        let path = std::env::temp_dir().join(format!("logtra-{}-stream.sock", std::process::id()));
+        let _ = remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let receiver = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            BufReader::new(stream)
+                .lines()
+                .take(2)
+                .map(|line| line.unwrap())
+                .collect::<Vec<String>>()
+        });
+
+        let decl = SinkDeclaration {
+            name: path.to_str().unwrap().to_string(),
+            template: "[%i]: %m".to_string(),
+            ..Default::default()
+        };
+        let mut msg = message("first", LogSeverity::Info);
+        let mut sink = UnixSocketSink::new(decl, UnixSocketKind::Stream, Framing::Newline).unwrap();
+        sink.log_filtered(&msg);
+        msg.msg = "second";
+        sink.log_filtered(&msg);
+
+        assert_eq!(
+            vec!["[Info ]: first", "[Info ]: second"],
+            receiver.join().unwrap()
+        );
+        assert_eq!(0, sink.dropped());
+        assert!(remove_file(&path).is_ok());
+
+        // nothing listens anymore, so the msg is dropped
+        let mut sink = UnixSocketSink::new(
+            SinkDeclaration {
+                name: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            },
+            UnixSocketKind::Stream,
+            Framing::Newline,
+        )
+        .unwrap();
+        sink.log_filtered(&msg);
+        assert_eq!(1, sink.dropped());
+
+        let path = std::env::temp_dir().join(format!("logtra-{}-dgram.sock", std::process::id()));
+        let _ = remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let mut sink = UnixSocketSink::new(
+            SinkDeclaration {
+                name: path.to_str().unwrap().to_string(),
+                template: "%m".to_string(),
+                ..Default::default()
+            },
+            UnixSocketKind::Datagram,
+            Framing::LengthPrefixed,
+        )
+        .unwrap();
+        sink.log_filtered(&msg);
+
+        let mut buffer = [0u8; 64];
+        let received = socket.recv(&mut buffer).unwrap();
+        assert_eq!(b"\x00\x00\x00\x06second", &buffer[..received]);
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn split_records_work() {
+        use std::os::unix::net::UnixDatagram;
+
+        use super::{split_records, Framing, UnixSocketKind, UnixSocketSink, CONTINUATION_MARKER};
+
+        let reassemble = |records: &[String]| {
+            records
+                .iter()
+                .map(|record| record.strip_suffix(CONTINUATION_MARKER).unwrap_or(record))
+                .collect::<String>()
+        };
+
+        assert_eq!(vec!["short"], split_records("short".to_string(), 5));
+        let records = split_records("abcdefghij".to_string(), 4);
+        assert_eq!(vec!["abc\\", "def\\", "ghij"], records);
+        assert_eq!("abcdefghij", reassemble(&records));
+        assert_eq!(vec!["a\\", "b\\", "c"], split_records("abc".to_string(), 1));
+
+        // every 'ä' takes two bytes, none of them may be cut in half
+        let text = "äääää".to_string();
+        let records = split_records(text.clone(), 6);
+        assert_eq!(vec!["ää\\", "äää"], records);
+        assert!(records.iter().all(|record| record.len() <= 6));
+        assert_eq!(text, reassemble(&records));
+
+        let path = std::env::temp_dir().join(format!("logtra-{}-split.sock", std::process::id()));
+        let _ = remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let mut sink = UnixSocketSink::new(
+            SinkDeclaration {
+                name: path.to_str().unwrap().to_string(),
+                template: "%m".to_string(),
+                ..Default::default()
+            },
+            UnixSocketKind::Datagram,
+            Framing::Newline,
+        )
+        .unwrap()
+        .with_max_record_size(16);
+        let msg = message(
+            "a msg which does not fit into a single datagram",
+            LogSeverity::Info,
+        );
+        sink.log_filtered(&msg);
+
+        let mut records = Vec::new();
+        let mut buffer = [0u8; 64];
+        for _ in 0..4 {
+            let received = socket.recv(&mut buffer).unwrap();
+            let record = std::str::from_utf8(&buffer[..received]).unwrap();
+            records.push(record.strip_suffix('\n').unwrap().to_string());
+        }
+        assert!(records.iter().all(|record| record.len() <= 16));
+        assert_eq!(msg.msg, reassemble(&records));
+        assert_eq!(0, sink.dropped());
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[cfg(feature = "otlp")]
+    #[test]
+    fn otlp_sink_works() {
+        use std::{
+            io::{BufRead, BufReader, Read, Write},
+            net::TcpListener,
+        };
+
+        use crate::sink::OtlpSink;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/logs", listener.local_addr().unwrap());
+        let collector = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header == "\r\n" {
+                    break;
+                }
+                if let Some(value) = header.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let mut sink = OtlpSink::new(
+            SinkDeclaration {
+                name: endpoint,
+                template: "%m".to_string(),
+                ..Default::default()
+            },
+            2,
+        )
+        .unwrap();
+        let mut msg = LogMessage {
+            file: "src/sink.rs",
+            line: 42,
+            color: Color::Orange,
+            ..message("otlp: \"disk\" almost full", LogSeverity::Warn)
+        };
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Info;
+        msg.msg = "otlp: cleaned up";
+        msg.fields = &[("freed", "2 GiB"), ("volume", "/var")];
+        sink.log_filtered(&msg);
+
+        let body = collector.join().unwrap();
+        assert_eq!(0, sink.dropped());
+        assert!(body.contains(
+            "\"severityNumber\":13,\"severityText\":\"Warn\",\"body\":{\"stringValue\":\"otlp: \\\"disk\\\" almost full\"}"
+        ));
+        assert!(body.contains("{\"key\":\"code.lineno\",\"value\":{\"intValue\":\"42\"}}"));
+        assert!(body.contains("\"severityNumber\":9"));
+        assert!(body.contains(
+            "{\"key\":\"freed\",\"value\":{\"stringValue\":\"2 GiB\"}},{\"key\":\"volume\",\"value\":{\"stringValue\":\"/var\"}}]}"
+        ));
+
+        let endpoint = |name: &str| {
+            let sink = OtlpSink::new(
+                SinkDeclaration {
+                    name: name.to_string(),
+                    ..Default::default()
+                },
+                2,
+            )
+            .unwrap();
+            (sink.host.clone(), sink.path.clone())
+        };
+        assert_eq!(
+            ("collector:4318".to_string(), "/v1/logs".to_string()),
+            endpoint("http://collector/v1/logs")
+        );
+        assert_eq!(
+            ("collector:4318".to_string(), "/v1/logs".to_string()),
+            endpoint("http://collector")
+        );
+        assert_eq!(
+            ("[::1]:9000".to_string(), "/logs".to_string()),
+            endpoint("http://[::1]:9000/logs")
+        );
+        assert!(OtlpSink::new(SinkDeclaration::default(), 2).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_sink_write_timeout_works() {
+        use std::{
+            io::{BufRead, BufReader},
+            os::unix::net::UnixListener,
+            thread,
+            time::Instant,
+        };
+
+        use super::{Framing, UnixSocketKind, UnixSocketSink};
+
+        let path = std::env::temp_dir().join(format!("logtra-{}-stalled.sock", std::process::id()));
+        let _ = remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let payload = "x".repeat(64 * 1024);
+        let mut msg = message(&payload, LogSeverity::Info);
+        let mut sink = UnixSocketSink::new(
+            SinkDeclaration {
+                name: path.to_str().unwrap().to_string(),
+                template: "%m".to_string(),
+                ..Default::default()
+            },
+            UnixSocketKind::Stream,
+            Framing::Newline,
+        )
+        .unwrap()
+        .with_write_timeout(Duration::from_millis(50));
+
+        // the first connection is accepted but never read, until the socket buffer is full
+        sink.log_filtered(&msg);
+        let (stalled, _) = listener.accept().unwrap();
+        let start = Instant::now();
+        for _ in 0..1000 {
+            if sink.dropped() > 0 {
+                break;
+            }
+            sink.log_filtered(&msg);
+        }
+        assert_eq!(1, sink.dropped());
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        let receiver = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            BufReader::new(stream)
+                .lines()
+                .map(|line| line.unwrap())
+                .next()
+        });
+        msg.msg = "recovered";
+        sink.log_filtered(&msg);
+        assert_eq!(Some("recovered".to_string()), receiver.join().unwrap());
+        assert_eq!(1, sink.dropped());
+
+        drop(stalled);
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gzip_file_sink_works() {
+        use std::{fs::File, io::Read};
+
+        use flate2::read::MultiGzDecoder;
+
+        use super::GzipFileSink;
+
+        let path = std::env::temp_dir().join(format!("logtra-{}.log.gz", std::process::id()));
+        let _ = remove_file(&path);
+        let decl = SinkDeclaration {
+            name: path.to_str().unwrap().to_string(),
+            template: "[%i]: %m\n".to_string(),
+            flush_severity: Some(LogSeverity::Error),
+            ..Default::default()
+        };
+        let mut msg = message("first", LogSeverity::Info);
+        let decompress = || {
+            let mut decompressed = Vec::new();
+            // an unfinished member ends unexpectedly, but everything synced is readable
+            let _ = MultiGzDecoder::new(File::open(&path).unwrap()).read_to_end(&mut decompressed);
+            String::from_utf8(decompressed).unwrap()
+        };
+
+        let mut sink = GzipFileSink::new(decl.clone()).unwrap();
+        sink.log_filtered(&msg);
+        msg.msg = "second";
+        msg.severity = LogSeverity::Error;
+        sink.log_filtered(&msg);
+        assert_eq!("[Info ]: first\n[Error]: second\n", decompress());
+
+        msg.msg = "third";
+        msg.severity = LogSeverity::Info;
+        sink.log_filtered(&msg);
+        drop(sink);
+        assert_eq!(
+            "[Info ]: first\n[Error]: second\n[Info ]: third\n",
+            decompress()
+        );
+
+        let mut sink = GzipFileSink::new(decl).unwrap();
+        msg.msg = "appended";
+        sink.log_filtered(&msg);
+        assert_eq!(0, sink.dropped());
+        drop(sink);
+        assert_eq!(
+            "[Info ]: first\n[Error]: second\n[Info ]: third\n[Info ]: appended\n",
+            decompress()
+        );
+        assert!(remove_file(&path).is_ok());
+
+        assert!(GzipFileSink::new(SinkDeclaration::default()).is_err());
+    }
+
+    #[cfg(feature = "eventlog")]
+    #[test]
+    fn event_log_mapping_works() {
+        use super::{event_message, EventType};
+
+        assert_eq!(
+            EventType::Information,
+            EventType::from_severity(LogSeverity::Trace)
+        );
+        assert_eq!(
+            EventType::Information,
+            EventType::from_severity(LogSeverity::Info)
+        );
+        assert_eq!(
+            EventType::Warning,
+            EventType::from_severity(LogSeverity::Warn)
+        );
+        assert_eq!(
+            EventType::Error,
+            EventType::from_severity(LogSeverity::Fatal)
+        );
+
+        let decl = SinkDeclaration {
+            name: "logtra".to_string(),
+            template: "[%i]: %m".to_string(),
+            ..Default::default()
         };
         let msg = LogMessage {
-            time: DateTime::<Utc>::default().into(),
-            module: "logtra",
-            file: file!(),
-            line: line!(),
-            msg: "Hello world!",
-            severity: LogSeverity::Info,
-            color: Color::Red,
+            color: Color::Orange,
+            ..message("Grüße", LogSeverity::Warn)
         };
+        let message = event_message(&decl, &msg);
+        assert_eq!(Some(&0), message.last());
+        assert_eq!(
+            "[Warn ]: Grüße",
+            String::from_utf16(&message[..message.len() - 1]).unwrap()
+        );
+    }
 
-        {
-            let mut sink = FileSink::new(decl);
-            sink.log(&msg);
-            sink.log(&msg);
-            sink.log(&msg);
-            sink.log(&msg);
+    #[test]
+    fn coalesce_sink_works() {
+        use std::{
+            collections::hash_map::DefaultHasher,
+            hash::{Hash, Hasher},
+            time::{SystemTime, UNIX_EPOCH},
+        };
+
+        use super::{CoalesceSink, MemorySink};
+
+        let memory = MemorySink::new(SinkDeclaration {
+            template: "%m".to_string(),
+            ..Default::default()
+        });
+        let lines = memory.lines();
+        let mut sink = CoalesceSink::new(memory, Duration::from_secs(1), |msg| {
+            let mut hasher = DefaultHasher::new();
+            (msg.file, msg.line).hash(&mut hasher);
+            hasher.finish()
+        });
+
+        let start: SystemTime = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut msg = LogMessage {
+            time: start,
+            file: "net.rs",
+            line: 42,
+            color: Color::Orange,
+            ..message("connection reset", LogSeverity::Warn)
+        };
+        for i in 0..523 {
+            msg.time = start + Duration::from_millis(i);
+            sink.log_filtered(&msg);
         }
+        assert!(lines.lock().unwrap().is_empty());
 
-        assert!(remove_file(Path::new("example.log")).is_ok());
+        msg.time = start + Duration::from_secs(2);
+        msg.line = 7;
+        msg.msg = "shutting down";
+        sink.log_filtered(&msg);
+        assert_eq!(
+            vec!["connection reset occurred 523 times between 1970-01-12T13:46:40+00:00 and 1970-01-12T13:46:40.522+00:00"],
+            *lines.lock().unwrap()
+        );
+
+        assert!(sink.flush().is_ok());
+        assert_eq!("shutting down", lines.lock().unwrap()[1]);
+        assert_eq!(2, lines.lock().unwrap().len());
+    }
+
+    #[test]
+    fn affix_sink_works() {
+        use super::{AffixSink, MemorySink};
+
+        let memory = MemorySink::new(SinkDeclaration {
+            template: "[%i]: %m\n".to_string(),
+            ..Default::default()
+        });
+        let lines = memory.lines();
+        let mut sink = AffixSink::new(memory, "[worker-3] ", " (100%)").unwrap();
+
+        let mut msg = message("first", LogSeverity::Info);
+        sink.log_filtered(&msg);
+        msg.msg = "second";
+        sink.log_filtered(&msg);
+
+        assert_eq!(
+            vec![
+                "[worker-3] [Info ]: first (100%)\n",
+                "[worker-3] [Info ]: second (100%)\n"
+            ],
+            *lines.lock().unwrap()
+        );
+
+        struct Undeclared;
+        impl Sink for Undeclared {
+            fn log(&mut self, _msg: &LogMessage) {}
+            fn severity(&self) -> LogSeverity {
+                LogSeverity::Info
+            }
+            fn module(&self) -> &str {
+                ""
+            }
+        }
+        assert!(matches!(
+            AffixSink::new(Undeclared, "[", "]"),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn file_sink_flush_failure_works() {
+        use std::fs::{create_dir, read_to_string, remove_dir_all};
+
+        use super::{FlushFailure, FILE_SINK_BUFFER_SIZE};
+
+        let dir = std::env::temp_dir().join(format!("logtra-{}-flush-failure", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        let decl = SinkDeclaration {
+            name: dir
+                .join("missing")
+                .join("file.log")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            template: "%m\n".to_string(),
+            flush_severity: Some(LogSeverity::Info),
+            ..Default::default()
+        };
+        let mut msg = message("first", LogSeverity::Info);
+        create_dir(&dir).unwrap();
+
+        // retained until the directory exists
+        let mut sink = FileSink::new(decl.clone()).unwrap();
+        msg.msg = "retained";
+        sink.log_filtered(&msg);
+        assert_eq!(1, sink.index);
+        create_dir(dir.join("missing")).unwrap();
+        msg.msg = "recovered";
+        sink.log_filtered(&msg);
+        assert_eq!(0, sink.dropped());
+        assert_eq!("retained\nrecovered\n", read_to_string(&decl.name).unwrap());
+        drop(sink);
+        remove_dir_all(dir.join("missing")).unwrap();
+
+        // retained up to the buffer size
+        let mut sink = FileSink::new(SinkDeclaration {
+            flush_severity: None,
+            ..decl.clone()
+        })
+        .unwrap();
+        for _ in 0..FILE_SINK_BUFFER_SIZE + 5 {
+            sink.log_filtered(&msg);
+        }
+        assert_eq!(5, sink.dropped());
+        std::mem::forget(sink);
+
+        // discarded
+        let mut sink = FileSink::new(decl.clone())
+            .unwrap()
+            .with_flush_failure(FlushFailure::Discard);
+        msg.msg = "discarded";
+        sink.log_filtered(&msg);
+        sink.log_filtered(&msg);
+        assert_eq!(2, sink.dropped());
+        assert_eq!(0, sink.index);
+        std::mem::forget(sink);
+
+        // spilled to the fallback path
+        let fallback = dir.join("fallback.log");
+        let mut sink = FileSink::new(decl.clone())
+            .unwrap()
+            .with_flush_failure(FlushFailure::Spill(fallback.to_str().unwrap().to_string()));
+        msg.msg = "spilled";
+        sink.log_filtered(&msg);
+        sink.log_filtered(&msg);
+        assert_eq!(0, sink.dropped());
+        assert_eq!("spilled\nspilled\n", read_to_string(&fallback).unwrap());
+        std::mem::forget(sink);
+
+        // dropped if spilling fails as well
+        let mut sink = FileSink::new(decl)
+            .unwrap()
+            .with_flush_failure(FlushFailure::Spill(
+                dir.join("missing")
+                    .join("fallback.log")
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            ));
+        sink.log_filtered(&msg);
+        assert_eq!(1, sink.dropped());
+        std::mem::forget(sink);
+
+        assert!(remove_dir_all(&dir).is_ok());
+    }
+
+    #[test]
+    fn file_sink_keeps_every_msg_across_flushes() {
+        use std::fs::{read_to_string, remove_file};
+
+        let path = std::env::temp_dir().join(format!("logtra-{}-2500.log", std::process::id()));
+        let _ = remove_file(&path);
+        let mut sink = FileSink::new(SinkDeclaration {
+            name: path.to_str().unwrap().to_string(),
+            template: "%m\n".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let lines: Vec<String> = (0..2500).map(|i| format!("msg {}", i)).collect();
+        for line in lines.iter() {
+            sink.log_filtered(&message(line, LogSeverity::Info));
+        }
+        drop(sink);
+
+        let written = read_to_string(&path).unwrap();
+        assert_eq!(lines, written.lines().collect::<Vec<&str>>());
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[test]
+    fn file_sink_appends() {
+        use std::fs::{read_to_string, remove_file};
+
+        let path = std::env::temp_dir().join(format!("logtra-{}-append.log", std::process::id()));
+        let _ = remove_file(&path);
+        let decl = SinkDeclaration {
+            name: path.to_str().unwrap().to_string(),
+            template: "%m\n".to_string(),
+            flush_severity: Some(LogSeverity::Error),
+            ..Default::default()
+        };
+        let mut msg = message("buffered", LogSeverity::Info);
+
+        let mut sink = FileSink::new(decl.clone()).unwrap();
+        for _ in 0..3 {
+            msg.severity = LogSeverity::Info;
+            sink.log_filtered(&msg);
+            sink.log_filtered(&msg);
+            msg.severity = LogSeverity::Error;
+            sink.log_filtered(&msg);
+        }
+        assert_eq!(9, read_to_string(&path).unwrap().lines().count());
+        drop(sink);
+
+        // a new sink, e.g. after a restart, keeps the previous msgs as well
+        let mut sink = FileSink::new(decl).unwrap();
+        sink.log_filtered(&msg);
+        drop(sink);
+        assert_eq!(10, read_to_string(&path).unwrap().lines().count());
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[test]
+    fn file_sink_strips_colors() {
+        use std::fs::{read, remove_file};
+
+        let path =
+            std::env::temp_dir().join(format!("logtra-{}-colorless.log", std::process::id()));
+        let _ = remove_file(&path);
+        let decl = SinkDeclaration {
+            name: path.to_str().unwrap().to_string(),
+            template: "[%[%i%]]: %m\n".to_string(),
+            strip_colors: true,
+            ..Default::default()
+        };
+        let msg = message("no escapes", LogSeverity::Warn);
+
+        let mut sink = FileSink::new(decl).unwrap();
+        sink.log_filtered(&msg);
+        drop(sink);
+        let written = read(&path).unwrap();
+        assert!(!written.contains(&0x1b));
+        assert_eq!(b"[Warn ]: no escapes\n", written.as_slice());
+        assert!(remove_file(&path).is_ok());
+    }
+
+    #[test]
+    fn rolling_file_sink_works() {
+        use super::RollingFileSink;
+        use std::fs::{metadata, read_to_string, remove_dir_all};
+
+        let directory = std::env::temp_dir().join(format!("logtra-{}-rolling", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("app.log");
+        let decl = SinkDeclaration {
+            name: path.to_str().unwrap().to_string(),
+            template: "%m\n".to_string(),
+            ..Default::default()
+        };
+        let mut msg = message("0123456789", LogSeverity::Info);
+        let rotated = |index: usize| directory.join(format!("app.log.{}", index));
+
+        // three msgs of 11 bytes fit into a file
+        let mut sink = RollingFileSink::new(decl.clone(), 40, 2).unwrap();
+        for _ in 0..10 {
+            sink.log_filtered(&msg);
+        }
+        assert_eq!(1, read_to_string(&path).unwrap().lines().count());
+        assert_eq!(3, read_to_string(rotated(1)).unwrap().lines().count());
+        assert_eq!(3, read_to_string(rotated(2)).unwrap().lines().count());
+        assert!(!rotated(3).exists());
+
+        // an oversized msg is written into a file of its own
+        msg.msg = "this msg is larger than the whole file may be";
+        sink.log_filtered(&msg);
+        assert_eq!(
+            "this msg is larger than the whole file may be\n",
+            read_to_string(&path).unwrap()
+        );
+        msg.msg = "0123456789";
+        sink.log_filtered(&msg);
+        assert_eq!("0123456789\n", read_to_string(&path).unwrap());
+        assert_eq!(0, sink.dropped());
+        drop(sink);
+
+        // a new sink continues the existing file
+        let mut sink = RollingFileSink::new(decl.clone(), 40, 2).unwrap();
+        sink.log_filtered(&msg);
+        assert_eq!(22, metadata(&path).unwrap().len());
+        drop(sink);
+
+        // rotated files modified more than a day ago are purged on the next rotation
+        let two_days_ago = std::time::SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+        std::fs::File::options()
+            .write(true)
+            .open(rotated(2))
+            .unwrap()
+            .set_modified(two_days_ago)
+            .unwrap();
+        let mut sink = RollingFileSink::new(decl.clone(), 40, 3)
+            .unwrap()
+            .with_max_age(Duration::from_secs(24 * 60 * 60));
+        sink.log_filtered(&msg);
+        assert!(rotated(2).exists());
+        sink.log_filtered(&msg);
+        assert!(rotated(1).exists());
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+        drop(sink);
+
+        assert!(RollingFileSink::new(decl, 0, 2).is_err());
+        assert!(remove_dir_all(&directory).is_ok());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn rolling_file_sink_compression_works() {
+        use std::{
+            fs::{read_to_string, remove_dir_all, File},
+            io::Read,
+        };
+
+        use flate2::read::GzDecoder;
+
+        use super::RollingFileSink;
+
+        let directory =
+            std::env::temp_dir().join(format!("logtra-{}-rolling-gzip", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("app.log");
+        let rotated = |name: &str| directory.join(name);
+        let decompress = |name: &str| {
+            let mut decompressed = String::new();
+            GzDecoder::new(File::open(rotated(name)).unwrap())
+                .read_to_string(&mut decompressed)
+                .unwrap();
+            decompressed
+        };
+        let mut msg = message("", LogSeverity::Info);
+
+        // two msgs of 8 bytes fit into a file
+        let mut sink = RollingFileSink::new(
+            SinkDeclaration {
+                name: path.to_str().unwrap().to_string(),
+                template: "%m\n".to_string(),
+                ..Default::default()
+            },
+            16,
+            2,
+        )
+        .unwrap()
+        .with_compression(true);
+        let lines: Vec<String> = (0..7).map(|i| format!("line {}:", i)).collect();
+        for line in lines.iter() {
+            msg.msg = line;
+            sink.log_filtered(&msg);
+        }
+        assert!(sink.flush().is_ok());
+
+        assert_eq!("line 6:\n", read_to_string(&path).unwrap());
+        assert_eq!("line 4:\nline 5:\n", decompress("app.log.1.gz"));
+        assert_eq!("line 2:\nline 3:\n", decompress("app.log.2.gz"));
+        for name in [
+            "app.log.1",
+            "app.log.2",
+            "app.log.3.gz",
+            "app.log.1.gz.partial",
+        ] {
+            assert!(!rotated(name).exists(), "{}", name);
+        }
+        assert_eq!(0, sink.dropped());
+
+        drop(sink);
+        assert!(remove_dir_all(&directory).is_ok());
+    }
+
+    #[test]
+    fn timed_rolling_file_sink_works() {
+        use super::{RollInterval, TimedRollingFileSink};
+        use std::fs::{read_to_string, remove_dir_all};
+
+        let directory = std::env::temp_dir().join(format!("logtra-{}-timed", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let decl = SinkDeclaration {
+            name: directory
+                .join("app-%Y-%m-%d.log")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            template: "%m\n".to_string(),
+            ..Default::default()
+        };
+        let day = |rfc3339: &str| DateTime::parse_from_rfc3339(rfc3339).unwrap().into();
+        let mut msg = LogMessage {
+            time: day("2023-03-01T23:59:59Z"),
+            ..message("before midnight", LogSeverity::Info)
+        };
+
+        let mut sink = TimedRollingFileSink::new(decl.clone(), RollInterval::Daily).unwrap();
+        sink.log_filtered(&msg);
+        msg.time = day("2023-03-02T00:00:00Z");
+        msg.msg = "after midnight";
+        sink.log_filtered(&msg);
+        msg.time = day("2023-03-02T13:00:00Z");
+        msg.msg = "at noon";
+        sink.log_filtered(&msg);
+        drop(sink);
+
+        assert_eq!(
+            "before midnight\n",
+            read_to_string(directory.join("app-2023-03-01.log")).unwrap()
+        );
+        assert_eq!(
+            "after midnight\nat noon\n",
+            read_to_string(directory.join("app-2023-03-02.log")).unwrap()
+        );
+        assert_eq!(2, std::fs::read_dir(&directory).unwrap().count());
+
+        // only files named by the pattern whose day ended more than two days ago are purged, the 28th ended 49 hours ago
+        for name in ["app-2023-02-20.log", "app-2023-02-28.log", "notes.txt"] {
+            std::fs::write(directory.join(name), "").unwrap();
+        }
+        let mut sink = TimedRollingFileSink::new(decl.clone(), RollInterval::Daily)
+            .unwrap()
+            .with_max_age(std::time::Duration::from_secs(2 * 24 * 60 * 60));
+        msg.time = day("2023-03-03T01:00:00Z");
+        msg.msg = "purging";
+        sink.log_filtered(&msg);
+        drop(sink);
+        let mut names: Vec<String> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            vec![
+                "app-2023-03-01.log",
+                "app-2023-03-02.log",
+                "app-2023-03-03.log",
+                "notes.txt"
+            ],
+            names
+        );
+        assert_eq!(
+            Some(
+                chrono::NaiveDate::from_ymd_opt(2023, 3, 1)
+                    .unwrap()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+                    .and_utc()
+            ),
+            super::interval_start("app-2023-03-01.log", "app-%Y-%m-%d.log")
+        );
+        assert_eq!(None, super::interval_start("notes.txt", "app-%Y-%m-%d.log"));
+
+        let decl = SinkDeclaration {
+            name: directory.join("app-%Q.log").to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        assert!(TimedRollingFileSink::new(decl, RollInterval::Hourly).is_err());
+        assert!(remove_dir_all(&directory).is_ok());
+    }
+
+    #[test]
+    fn writer_sink_works() {
+        use super::WriterSink;
+
+        let decl = SinkDeclaration {
+            name: "writer".to_string(),
+            template: "[%i][%s][%f:%l]: %m\n".to_string(),
+            ..Default::default()
+        };
+        let mut msg = LogMessage {
+            file: "src/main.rs",
+            line: 7,
+            ..message("first", LogSeverity::Info)
+        };
+
+        let mut sink = WriterSink::new(decl, Vec::new());
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Debug;
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Error;
+        msg.msg = "second";
+        sink.log_filtered(&msg);
+
+        assert_eq!(
+            "[Info ][logtra][src/main.rs:7]: first\n[Error][logtra][src/main.rs:7]: second\n",
+            String::from_utf8(sink.writer().clone()).unwrap()
+        );
+        assert_eq!(0, sink.dropped());
+    }
+
+    #[test]
+    fn fmt_writer_sink_works() {
+        use super::FmtWriterSink;
+
+        /// Rejects every write, like a UART whose transmit buffer is full
+        struct FullWriter;
+
+        impl std::fmt::Write for FullWriter {
+            fn write_str(&mut self, _s: &str) -> std::fmt::Result {
+                Err(std::fmt::Error)
+            }
+        }
+
+        let decl = SinkDeclaration {
+            name: "fmt".to_string(),
+            template: "[%i]: %m\n".to_string(),
+            ..Default::default()
+        };
+        let mut msg = message("first", LogSeverity::Info);
+
+        let mut sink = FmtWriterSink::new(decl.clone(), String::new());
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Debug;
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Warn;
+        msg.msg = "second";
+        sink.log_filtered(&msg);
+
+        assert_eq!("[Info ]: first\n[Warn ]: second\n", sink.writer());
+        assert_eq!(0, sink.dropped());
+
+        let mut full = FmtWriterSink::new(decl, FullWriter);
+        full.log_filtered(&msg);
+        assert_eq!(1, full.dropped());
+    }
+
+    #[test]
+    fn tcp_sink_works() {
+        use std::{
+            io::{BufRead, BufReader},
+            net::TcpListener,
+            thread,
+        };
+
+        use super::TcpSink;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let receiver = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            BufReader::new(stream)
+                .lines()
+                .take(2)
+                .map(|line| line.unwrap())
+                .collect::<Vec<String>>()
+        });
+
+        let decl = SinkDeclaration {
+            name: address,
+            template: "[%i]: %m".to_string(),
+            ..Default::default()
+        };
+        let mut msg = message("first", LogSeverity::Info);
+        let mut sink = TcpSink::new(decl, 16).unwrap();
+        sink.log_filtered(&msg);
+        msg.msg = "second";
+        sink.log_filtered(&msg);
+
+        assert_eq!(
+            vec!["[Info ]: first", "[Info ]: second"],
+            receiver.join().unwrap()
+        );
+        assert_eq!(0, sink.pending());
+        assert_eq!(0, sink.dropped());
+    }
+
+    #[test]
+    fn tcp_sink_buffers_while_unreachable() {
+        use std::{
+            io::{BufRead, BufReader},
+            net::TcpListener,
+        };
+
+        use super::TcpSink;
+
+        // a port nothing listens on anymore
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let decl = SinkDeclaration {
+            name: address.to_string(),
+            template: "%m".to_string(),
+            ..Default::default()
+        };
+        let mut msg = message("", LogSeverity::Info);
+        let mut sink = TcpSink::new(decl, 2)
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        let lines: Vec<String> = (0..5).map(|i| format!("line {}", i)).collect();
+        for line in lines.iter() {
+            msg.msg = line;
+            sink.log_filtered(&msg);
+        }
+        assert_eq!(2, sink.pending());
+        assert_eq!(3, sink.dropped());
+        assert!(matches!(sink.flush(), Err(Error::Io(_))));
+
+        // the kept lines are sent once the peer is reachable again
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            // the port was taken in the meantime
+            Err(_) => return,
+        };
+        assert!(sink.flush().is_ok());
+        let (stream, _) = listener.accept().unwrap();
+        let received: Vec<String> = BufReader::new(stream)
+            .lines()
+            .take(2)
+            .map(|line| line.unwrap())
+            .collect();
+        assert_eq!(vec!["line 3", "line 4"], received);
+    }
+
+    #[test]
+    fn udp_sink_works() {
+        use std::net::UdpSocket;
+
+        use super::{truncate_with_ellipsis, UdpSink};
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let decl = SinkDeclaration {
+            name: receiver.local_addr().unwrap().to_string(),
+            template: "[%i]: %m".to_string(),
+            ..Default::default()
+        };
+        let mut msg = message("fire and forget", LogSeverity::Info);
+
+        let mut sink = UdpSink::new(decl).unwrap().with_mtu(16);
+        let mut buffer = [0u8; 64];
+        msg.msg = "short";
+        sink.log_filtered(&msg);
+        let received = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(b"[Info ]: short", &buffer[..received]);
+
+        msg.msg = "does not fit into a datagram";
+        sink.log_filtered(&msg);
+        let received = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(b"[Info ]: does...", &buffer[..received]);
+        assert_eq!(0, sink.dropped());
+
+        // every 'ä' takes two bytes, none of them may be cut in half
+        assert_eq!("ää...", truncate_with_ellipsis("äääää".to_string(), 8));
+        assert!(UdpSink::new(SinkDeclaration::default()).is_err());
+    }
+
+    #[test]
+    fn syslog_priority_works() {
+        use super::{syslog_priority, SyslogFacility};
+
+        for (severity, expected) in [
+            (LogSeverity::Fatal, 2),
+            (LogSeverity::Error, 3),
+            (LogSeverity::Warn, 4),
+            (LogSeverity::Info, 6),
+            (LogSeverity::Debug, 7),
+            (LogSeverity::Trace, 7),
+        ] {
+            assert_eq!(expected, syslog_priority(SyslogFacility::Kern, severity));
+            assert_eq!(
+                16 * 8 + expected,
+                syslog_priority(SyslogFacility::Local0, severity)
+            );
+        }
+    }
+
+    #[test]
+    fn syslog_sink_works() {
+        use std::net::UdpSocket;
+
+        use super::{SyslogFacility, SyslogSink};
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let decl = SinkDeclaration {
+            name: receiver.local_addr().unwrap().to_string(),
+            template: "%m".to_string(),
+            ..Default::default()
+        };
+        let msg = message("disk almost full", LogSeverity::Warn);
+
+        let mut sink = SyslogSink::udp(decl, SyslogFacility::User)
+            .unwrap()
+            .with_app_name("my app");
+        sink.log_filtered(&msg);
+        let mut buffer = [0u8; 512];
+        let received = receiver.recv(&mut buffer).unwrap();
+        let datagram = String::from_utf8_lossy(&buffer[..received]).into_owned();
+
+        let fields: Vec<&str> = datagram.splitn(8, ' ').collect();
+        assert_eq!("<12>1", fields[0]);
+        assert_eq!("1970-01-01T00:00:00.000000Z", fields[1]);
+        assert!(!fields[2].is_empty());
+        assert_eq!("my_app", fields[3]);
+        assert_eq!(std::process::id().to_string(), fields[4]);
+        assert_eq!(["-", "-", "disk almost full"], fields[5..]);
+        assert_eq!(0, sink.dropped());
+    }
+
+    #[test]
+    fn http_sink_works() {
+        use std::{
+            io::{BufRead, BufReader, Read, Write},
+            net::TcpListener,
+        };
+
+        use super::HttpSink;
+
+        // answers every request with the next status, handing the head and body of the request over
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (requests, received) = std::sync::mpsc::channel();
+        let server = std::thread::spawn(move || {
+            for status in ["200 OK", "500 Internal Server Error"] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push(line.trim_end().to_string());
+                }
+                let length: usize = head
+                    .iter()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n",
+                    status
+                )
+                .unwrap();
+                requests
+                    .send((head, String::from_utf8(body).unwrap()))
+                    .unwrap();
+            }
+        });
+
+        let decl = SinkDeclaration {
+            name: format!("http://{}/alerts", address),
+            severity: LogSeverity::Error,
+            strip_location: true,
+            ..Default::default()
+        };
+        let mut msg = message("disk \"data\" is full", LogSeverity::Error);
+
+        let mut sink = HttpSink::new(decl.clone(), 4).unwrap().with_retries(0);
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Warn;
+        sink.log_filtered(&msg);
+        msg.severity = LogSeverity::Fatal;
+        sink.log_filtered(&msg);
+        assert!(sink.flush().is_ok());
+        server.join().unwrap();
+
+        let (head, body) = received.recv().unwrap();
+        assert_eq!("POST /alerts HTTP/1.1", head[0]);
+        assert!(head.contains(&"Content-Type: application/json".to_string()));
+        assert_eq!(
+            "{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"Error\",\"module\":\"logtra\",\"message\":\"disk \\\"data\\\" is full\"}",
+            body
+        );
+        // the warning is below the threshold, the fatal msg got the error status and no retry
+        let (_, body) = received.recv().unwrap();
+        assert!(body.contains("\"level\":\"Fatal\""));
+        assert!(received.try_recv().is_err());
+        assert_eq!(1, sink.dropped());
+
+        // a server which never answers stalls the thread of the sink, but never the logging thread
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (accepted, accepted_receiver) = std::sync::mpsc::channel();
+        let (release, release_receiver) = std::sync::mpsc::channel::<()>();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            accepted.send(()).unwrap();
+            let _ = release_receiver.recv();
+            drop(stream);
+        });
+        let mut sink = HttpSink::new(
+            SinkDeclaration {
+                name: format!("http://{}/alerts", address),
+                ..decl
+            },
+            1,
+        )
+        .unwrap()
+        .with_retries(0);
+        sink.log_filtered(&msg);
+        accepted_receiver.recv().unwrap();
+        // the first msg is being POSTed, the second one is queued and the third one does not fit
+        sink.log_filtered(&msg);
+        sink.log_filtered(&msg);
+        assert_eq!(1, sink.dropped());
+        release.send(()).unwrap();
+        server.join().unwrap();
+        assert!(sink.flush().is_ok());
+        // the stalled POST got no answer and the queued one no connection
+        assert_eq!(3, sink.dropped());
+
+        assert!(HttpSink::new(
+            SinkDeclaration {
+                name: "https://example.com".to_string(),
+                ..Default::default()
+            },
+            4
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_sink_works() {
+        use super::JsonSink;
+
+        let decl = SinkDeclaration {
+            name: "json".to_string(),
+            ..Default::default()
+        };
+        let mut msg = LogMessage {
+            module: "logtra::json",
+            file: "src/main.rs",
+            line: 7,
+            ..message("said \"hi\"\n\tand left \\o/ \u{1}", LogSeverity::Warn)
+        };
+
+        let mut sink = JsonSink::new(decl, Vec::new());
+        sink.log_filtered(&msg);
+        msg.msg = "tagged";
+        msg.tag = Some("audit");
+        sink.log_filtered(&msg);
+        msg.tag = None;
+        msg.msg = "with fields";
+        msg.fields = &[("status", "200"), ("path", "/\"x\"")];
+        sink.log_filtered(&msg);
+
+        let written = String::from_utf8(sink.writer().clone()).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(
+            vec![
+                "{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"Warn\",\"module\":\"logtra::json\",\"file\":\"src/main.rs\",\"line\":7,\"message\":\"said \\\"hi\\\"\\n\\tand left \\\\o/ \\u0001\"}",
+                "{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"Warn\",\"module\":\"logtra::json\",\"file\":\"src/main.rs\",\"line\":7,\"message\":\"tagged\",\"tag\":\"audit\"}",
+                "{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"Warn\",\"module\":\"logtra::json\",\"file\":\"src/main.rs\",\"line\":7,\"message\":\"with fields\",\"fields\":{\"status\":\"200\",\"path\":\"/\\\"x\\\"\"}}",
+            ],
+            lines
+        );
+        assert_eq!(0, sink.dropped());
+    }
+
+    #[test]
+    fn json_sink_array_batches_work() {
+        use super::JsonSink;
+
+        let decl = SinkDeclaration {
+            name: "json".to_string(),
+            strip_location: true,
+            flush_severity: Some(LogSeverity::Error),
+            ..Default::default()
+        };
+        let object = |level: &str, message: &str| {
+            format!(
+                "{{\"timestamp\":\"1970-01-01T00:00:00+00:00\",\"level\":\"{}\",\"module\":\"logtra\",\"message\":\"{}\"}}",
+                level, message
+            )
+        };
+        let mut msg = message("first", LogSeverity::Info);
+
+        let mut sink = JsonSink::new(decl, Vec::new()).with_array_batches(2);
+        assert!(sink.capabilities().flushable);
+        for text in ["first", "second", "third"] {
+            msg.msg = text;
+            sink.log_filtered(&msg);
+        }
+        // the first two msgs filled a batch, the third one waits for the flush
+        let written = String::from_utf8(sink.writer().clone()).unwrap();
+        assert_eq!(
+            format!(
+                "[{},{}]\n",
+                object("Info", "first"),
+                object("Info", "second")
+            ),
+            written
+        );
+        assert!(sink.flush().is_ok());
+        msg.severity = LogSeverity::Error;
+        msg.msg = "fourth";
+        sink.log_filtered(&msg);
+        assert!(sink.flush().is_ok());
+
+        let written = String::from_utf8(sink.writer().clone()).unwrap();
+        let arrays: Vec<&str> = written.lines().collect();
+        assert_eq!(
+            vec![
+                format!("[{},{}]", object("Info", "first"), object("Info", "second")),
+                format!("[{}]", object("Info", "third")),
+                format!("[{}]", object("Error", "fourth")),
+            ],
+            arrays
+        );
+        assert_eq!(0, sink.dropped());
+    }
+
+    #[test]
+    fn memory_sink_clones_share_lines() {
+        use super::CaptureSink;
+
+        let capture = CaptureSink::new(SinkDeclaration {
+            name: "capture".to_string(),
+            severity: LogSeverity::Info,
+            template: "[%i]: %m".to_string(),
+            ..Default::default()
+        });
+        let mut msg = message("first", LogSeverity::Info);
+
+        let mut sink = capture.clone();
+        sink.log_filtered(&msg);
+        msg.msg = "filtered";
+        msg.severity = LogSeverity::Debug;
+        sink.log_filtered(&msg);
+        msg.msg = "second";
+        msg.severity = LogSeverity::Error;
+        sink.log_filtered(&msg);
+
+        assert_eq!("[Info ]: first", capture.messages()[0]);
+        assert_eq!(
+            vec!["[Info ]: first", "[Error]: second"],
+            capture.messages()
+        );
+        capture.clear();
+        assert!(sink.messages().is_empty());
+    }
+
+    #[test]
+    fn fn_sink_works() {
+        use super::FnSink;
+
+        let counted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = counted.clone();
+        let mut sink = FnSink::new(
+            SinkDeclaration {
+                severity: LogSeverity::Warn,
+                module: "logtra".to_string(),
+                ..Default::default()
+            },
+            move |_msg: &LogMessage| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            },
+        );
+        let mut msg = message("called", LogSeverity::Warn);
+
+        for severity in [LogSeverity::Info, LogSeverity::Warn, LogSeverity::Error] {
+            msg.severity = severity;
+            sink.log_filtered(&msg);
+        }
+        msg.module = "other";
+        sink.log_filtered(&msg);
+
+        // only the warning and the error of the module reach the closure
+        assert_eq!(2, counted.load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[test]
+    fn async_sink_works() {
+        use std::sync::mpsc::{channel, Receiver, Sender};
+
+        use super::{AsyncSink, MemorySink};
+
+        /// Blocks on its first msg until released, like a sink writing to a stalled disk or network
+        struct StalledSink(MemorySink, Sender<()>, Option<Receiver<()>>);
+
+        impl Sink for StalledSink {
+            fn name(&self) -> &str {
+                self.0.name()
+            }
+
+            fn log(&mut self, msg: &LogMessage) {
+                if let Some(release) = self.2.take() {
+                    let _ = self.1.send(());
+                    let _ = release.recv();
+                }
+                self.0.log(msg);
+            }
+
+            fn severity(&self) -> LogSeverity {
+                self.0.severity()
+            }
+
+            fn set_severity(&mut self, severity: LogSeverity) {
+                self.0.set_severity(severity);
+            }
+
+            fn module(&self) -> &str {
+                self.0.module()
+            }
+        }
+
+        let capture = MemorySink::new(SinkDeclaration {
+            name: "async".to_string(),
+            severity: LogSeverity::Info,
+            template: "%m".to_string(),
+            ..Default::default()
+        });
+        let (stalled, stalled_receiver) = channel();
+        let (release, release_receiver) = channel();
+        let mut sink = AsyncSink::new(
+            StalledSink(capture.clone(), stalled, Some(release_receiver)),
+            16,
+        )
+        .unwrap();
+        let mut msg = message("", LogSeverity::Info);
+
+        let lines: Vec<String> = (0..5).map(|i| format!("async {}", i)).collect();
+        for line in lines.iter() {
+            msg.msg = line;
+            sink.log_filtered(&msg);
+        }
+        msg.severity = LogSeverity::Debug;
+        msg.msg = "filtered";
+        sink.log_filtered(&msg);
+        // every msg was queued while the inner sink is still stuck on the first one
+        stalled_receiver.recv().unwrap();
+        assert!(capture.messages().is_empty());
+        release.send(()).unwrap();
+
+        assert!(sink.flush().is_ok());
+        assert_eq!(lines, capture.messages());
+
+        // a severity change reaches the inner sink as well
+        sink.set_severity(LogSeverity::Debug);
+        sink.log_filtered(&msg);
+        drop(sink);
+        assert_eq!(
+            Some("filtered"),
+            capture.messages().last().map(String::as_str)
+        );
+
+        assert!(AsyncSink::new(MemorySink::new(SinkDeclaration::default()), 0).is_err());
     }
 }
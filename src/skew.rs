@@ -0,0 +1,136 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{clock, msg::LogMessage};
+
+/// The tag given to untagged msgs whose timestamp is skewed
+pub const SKEW_TAG: &str = "clock-skew";
+
+/// The threshold in milliseconds, 0 disables the check
+static THRESHOLD: AtomicU64 = AtomicU64::new(0);
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Enables the clock skew check for every published msg, or disables it with `None`
+/// A msg whose timestamp differs from the time of the [crate::clock::Clock] by more than the threshold triggers a single warning on stderr,
+/// and is tagged with [crate::skew::SKEW_TAG] unless it already carries a tag.
+pub fn set_skew_threshold(threshold: Option<Duration>) {
+    let millis = threshold.map(|threshold| threshold.as_millis().clamp(1, u64::MAX as u128) as u64);
+    THRESHOLD.store(millis.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Returns whether the skew warning was already emitted
+pub fn skew_warned() -> bool {
+    WARNED.load(Ordering::Relaxed)
+}
+
+#[doc(hidden)]
+/// Used by publish! to detect msgs with a skewed timestamp
+/// Returns the tagged copy of a skewed msg, which should be published instead.
+pub fn check<'a>(msg: &LogMessage<'a>) -> Option<LogMessage<'a>> {
+    let threshold = THRESHOLD.load(Ordering::Relaxed);
+    if threshold == 0 {
+        return None;
+    }
+
+    let now = clock::now();
+    let skew = match msg.time.duration_since(now) {
+        Ok(ahead) => ahead,
+        Err(err) => err.duration(),
+    };
+    if skew <= Duration::from_millis(threshold) {
+        return None;
+    }
+
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "logtra: msg timestamp is skewed by {:?} from the current time, check the clock configuration",
+            skew
+        );
+    }
+
+    let mut msg = *msg;
+    msg.tag = msg.tag.or(Some(SKEW_TAG));
+    Some(msg)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{
+        clock::{override_clock, Clock},
+        msg,
+        msg::LogSeverity,
+        publish,
+        sink::{MemorySink, SinkDeclaration},
+        skew::{set_skew_threshold, skew_warned},
+    };
+
+    /// Stands still at the epoch, like a board whose clock was never set
+    struct StoppedClock;
+
+    impl Clock for StoppedClock {
+        fn now(&self) -> SystemTime {
+            SystemTime::UNIX_EPOCH
+        }
+    }
+
+    #[test]
+    fn skew_is_detected() {
+        let sink = MemorySink::new(SinkDeclaration {
+            name: "skew".to_string(),
+            severity: LogSeverity::Info,
+            module: module_path!().to_string(),
+            template: "%g|%m".to_string(),
+            ..Default::default()
+        });
+        let lines = sink.lines();
+        crate::sink!(sink);
+        set_skew_threshold(Some(Duration::from_secs(60)));
+
+        let mut msg = msg!(Info, Default, "skew: on time");
+        publish!(&msg);
+        assert!(!skew_warned());
+
+        msg.time = SystemTime::now() - Duration::from_secs(3600);
+        msg.msg = "skew: an hour behind";
+        publish!(&msg);
+        assert!(skew_warned());
+
+        msg.time = SystemTime::now() + Duration::from_secs(3600);
+        msg.msg = "skew: an hour ahead";
+        msg.tag = Some("auth");
+        publish!(&msg);
+        assert!(skew_warned());
+
+        // compared with the clock the msgs are timestamped by, not with the system time
+        override_clock(Some(Arc::new(StoppedClock)));
+        let stopped = msg!(Info, Default, "skew: from a stopped clock");
+        assert!(crate::skew::check(&stopped).is_none());
+        publish!(&stopped);
+        override_clock(None);
+        set_skew_threshold(None);
+
+        let skewed: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.contains("skew: "))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec![
+                "|skew: on time",
+                "clock-skew|skew: an hour behind",
+                "auth|skew: an hour ahead",
+                "|skew: from a stopped clock"
+            ],
+            skewed
+        );
+    }
+}